@@ -0,0 +1,316 @@
+use chess::{File, Rank, Square, ALL_SQUARES};
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer::{self, Quad, Renderer as _};
+use iced::advanced::svg;
+use iced::advanced::widget::{tree, Tree, Widget};
+use iced::advanced::{Clipboard, Shell};
+use iced::event::{self, Event};
+use iced::mouse;
+use iced::widget::canvas::{self, Frame, Path, Stroke};
+use iced::{alignment, Border, Color, Element, Length, Pixels, Point, Rectangle, Renderer, Size, Theme, Vector};
+
+/// Space between squares, matching the spacing of the coordinate labels around the board.
+const SQUARE_GAP: f32 = 5.0;
+/// Gap between a piece image and the edge of its square, relative to the square.
+pub const PIECE_PADDING: f32 = 0.08;
+/// Arrows are sized relative to a square.
+const ARROW_WIDTH: f32 = 0.18;
+const ARROW_HEAD: f32 = 0.45;
+const ARROW_COLOR: Color = Color::from_rgba(0.9, 0.62, 0.0, 0.8);
+const VEIL_COLOR: Color = Color::from_rgba(0.0, 0.0, 0.0, 0.55);
+const BANNER_BUTTON_COLOR: Color = Color::from_rgb(0.2, 0.55, 0.3);
+
+/// How a piece is drawn: bundled artwork, or a letter as a fallback.
+pub enum PieceArt {
+    Image(svg::Handle),
+    Letter(String, Color),
+}
+
+/// What one square shows.
+pub struct SquareView {
+    pub fill: Color,
+    pub piece: Option<PieceArt>,
+    /// Border drawn around the square, as for the keyboard cursor.
+    pub outline: Option<Color>,
+}
+
+/// A notice laid across the board, such as the result once the game is over, with one
+/// button under it.
+pub struct Banner<Message> {
+    pub text: String,
+    pub button: &'static str,
+    pub on_press: Message,
+    /// Sent for a click anywhere else on the board, which puts the banner away.
+    pub on_dismiss: Message,
+}
+
+/// The whole board as a single widget: squares, pieces, and arrows are drawn in one pass, and
+/// clicks are translated back into the square under the pointer.
+pub struct BoardView<'a, Message> {
+    /// Indexed by `Square::to_index`.
+    pub squares: Vec<SquareView>,
+    pub arrows: &'a [(Square, Square)],
+    pub black_at_bottom: bool,
+    pub square_size: f32,
+    pub on_press: fn(Square) -> Message,
+    pub on_right_press: fn(Square) -> Message,
+    pub on_right_release: fn(Square) -> Message,
+    pub on_hover: fn(Square) -> Message,
+    pub on_unhover: fn(Square) -> Message,
+    pub banner: Option<Banner<Message>>,
+}
+
+/// The square under the pointer, remembered so that hover changes are reported once.
+#[derive(Default)]
+struct State {
+    hovered: Option<Square>,
+}
+
+impl<Message> BoardView<'_, Message> {
+    fn side(&self) -> f32 {
+        self.square_size * 8.0 + SQUARE_GAP * 7.0
+    }
+
+    /// Where `square` is drawn, relative to the board's top-left corner.
+    fn square_bounds(&self, square: Square) -> Rectangle {
+        let (file, rank) = (square.get_file().to_index(), square.get_rank().to_index());
+        let (column, row) = if self.black_at_bottom { (7 - file, rank) } else { (file, 7 - rank) };
+        let step = self.square_size + SQUARE_GAP;
+        Rectangle::new(
+            Point::new(column as f32 * step, row as f32 * step),
+            Size::new(self.square_size, self.square_size),
+        )
+    }
+
+    /// The square at `point`, relative to the board; the gaps between squares belong to none.
+    fn square_at(&self, point: Point) -> Option<Square> {
+        let step = self.square_size + SQUARE_GAP;
+        let (column, row) = ((point.x / step).floor(), (point.y / step).floor());
+        let on_square = |offset: f32, index: f32| {
+            (0.0..8.0).contains(&index) && offset - index * step <= self.square_size
+        };
+        if !on_square(point.x, column) || !on_square(point.y, row) {
+            return None;
+        }
+        let (column, row) = (column as usize, row as usize);
+        let (file, rank) = if self.black_at_bottom { (7 - column, row) } else { (column, 7 - row) };
+        Some(Square::make_square(Rank::from_index(rank), File::from_index(file)))
+    }
+
+    /// Where the banner's button sits, relative to the board, just below its middle.
+    fn banner_button_bounds(&self) -> Rectangle {
+        let size = Size::new(self.square_size * 3.0, self.square_size * 0.8);
+        let center = Point::new(self.side() / 2.0, self.side() / 2.0 + self.square_size * 0.6);
+        Rectangle::new(Point::new(center.x - size.width / 2.0, center.y - size.height / 2.0), size)
+    }
+
+    fn draw_banner(&self, frame: &mut Frame, banner: &Banner<Message>) {
+        frame.fill_rectangle(Point::ORIGIN, frame.size(), VEIL_COLOR);
+        let text = |content: &str, position: Point, size: f32| canvas::Text {
+            content: content.to_string(),
+            position,
+            color: Color::WHITE,
+            size: Pixels(size),
+            horizontal_alignment: alignment::Horizontal::Center,
+            vertical_alignment: alignment::Vertical::Center,
+            ..canvas::Text::default()
+        };
+        let center = frame.center();
+        frame.fill_text(text(
+            &banner.text,
+            Point::new(center.x, center.y - self.square_size * 0.5),
+            self.square_size * 0.4,
+        ));
+        let button = self.banner_button_bounds();
+        frame.fill_rectangle(button.position(), button.size(), BANNER_BUTTON_COLOR);
+        frame.fill_text(text(banner.button, button.center(), self.square_size * 0.3));
+    }
+
+    fn draw_letters(&self, frame: &mut Frame) {
+        for square in ALL_SQUARES {
+            if let Some(PieceArt::Letter(letter, color)) = &self.squares[square.to_index()].piece {
+                frame.fill_text(canvas::Text {
+                    content: letter.clone(),
+                    position: self.square_bounds(square).center(),
+                    color: *color,
+                    size: Pixels(self.square_size * 0.6),
+                    horizontal_alignment: alignment::Horizontal::Center,
+                    vertical_alignment: alignment::Vertical::Center,
+                    ..canvas::Text::default()
+                });
+            }
+        }
+    }
+
+    fn draw_arrows(&self, frame: &mut Frame) {
+        for &(from, to) in self.arrows {
+            let (start, end) = (self.square_bounds(from).center(), self.square_bounds(to).center());
+            let length = start.distance(end);
+            let direction = Vector::new((end.x - start.x) / length, (end.y - start.y) / length);
+            let head = ARROW_HEAD * self.square_size;
+            // The shaft stops where the head begins, so the tip stays sharp
+            let base = end - direction * head;
+            let across = Vector::new(-direction.y, direction.x) * (head / 2.0);
+            frame.stroke(
+                &Path::line(start, base),
+                Stroke::default().with_color(ARROW_COLOR).with_width(ARROW_WIDTH * self.square_size),
+            );
+            frame.fill(
+                &Path::new(|builder| {
+                    builder.move_to(end);
+                    builder.line_to(base + across);
+                    builder.line_to(base - across);
+                    builder.close();
+                }),
+                ARROW_COLOR,
+            );
+        }
+    }
+}
+
+impl<Message: Clone> Widget<Message, Theme, Renderer> for BoardView<'_, Message> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fixed(self.side()), Length::Fixed(self.side()))
+    }
+
+    fn layout(&self, _tree: &mut Tree, _renderer: &Renderer, _limits: &layout::Limits) -> layout::Node {
+        layout::Node::new(Size::new(self.side(), self.side()))
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let origin = Vector::new(bounds.x, bounds.y);
+        for square in ALL_SQUARES {
+            let view = &self.squares[square.to_index()];
+            let square_bounds = self.square_bounds(square) + origin;
+            renderer.fill_quad(
+                Quad {
+                    bounds: square_bounds,
+                    border: Border {
+                        radius: 3.0.into(),
+                        width: if view.outline.is_some() { 3.0 } else { 0.0 },
+                        color: view.outline.unwrap_or(Color::TRANSPARENT),
+                    },
+                    ..Quad::default()
+                },
+                view.fill,
+            );
+            if let Some(PieceArt::Image(piece)) = &view.piece {
+                let padding = self.square_size * PIECE_PADDING;
+                svg::Renderer::draw(renderer, piece.clone(), None, square_bounds.expand(-padding));
+            }
+        }
+
+        // A layer of its own keeps letters, arrows, and the banner above the squares
+        let has_letters = self.squares.iter().any(|view| matches!(view.piece, Some(PieceArt::Letter(..))));
+        if has_letters || !self.arrows.is_empty() || self.banner.is_some() {
+            renderer.with_layer(bounds, |renderer| {
+                let mut frame = Frame::new(renderer, bounds.size());
+                self.draw_letters(&mut frame);
+                self.draw_arrows(&mut frame);
+                if let Some(banner) = &self.banner {
+                    self.draw_banner(&mut frame, banner);
+                }
+                renderer.with_translation(origin, |renderer| {
+                    canvas::Renderer::draw(renderer, vec![frame.into_geometry()]);
+                });
+            });
+        }
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let Event::Mouse(event) = event else {
+            return event::Status::Ignored;
+        };
+        // The banner takes every click until it is answered or put away
+        if let Some(banner) = &self.banner {
+            let Some(point) = cursor.position_in(layout.bounds()) else {
+                return event::Status::Ignored;
+            };
+            if event != mouse::Event::ButtonPressed(mouse::Button::Left) {
+                return event::Status::Ignored;
+            }
+            shell.publish(if self.banner_button_bounds().contains(point) {
+                banner.on_press.clone()
+            } else {
+                banner.on_dismiss.clone()
+            });
+            return event::Status::Captured;
+        }
+        let square = cursor.position_in(layout.bounds()).and_then(|point| self.square_at(point));
+
+        let state = tree.state.downcast_mut::<State>();
+        if state.hovered != square {
+            if let Some(left) = state.hovered {
+                shell.publish((self.on_unhover)(left));
+            }
+            if let Some(entered) = square {
+                shell.publish((self.on_hover)(entered));
+            }
+            state.hovered = square;
+        }
+
+        let Some(square) = square else {
+            return event::Status::Ignored;
+        };
+        let message = match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => self.on_press,
+            mouse::Event::ButtonPressed(mouse::Button::Right) => self.on_right_press,
+            mouse::Event::ButtonReleased(mouse::Button::Right) => self.on_right_release,
+            _ => return event::Status::Ignored,
+        };
+        shell.publish(message(square));
+        event::Status::Captured
+    }
+
+    fn mouse_interaction(
+        &self,
+        _tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let Some(point) = cursor.position_in(layout.bounds()) else {
+            return mouse::Interaction::default();
+        };
+        let over_target = match self.banner {
+            Some(_) => self.banner_button_bounds().contains(point),
+            None => self.square_at(point).is_some(),
+        };
+        if over_target { mouse::Interaction::Pointer } else { mouse::Interaction::default() }
+    }
+}
+
+impl<'a, Message: Clone + 'a> From<BoardView<'a, Message>> for Element<'a, Message> {
+    fn from(board: BoardView<'a, Message>) -> Self {
+        Element::new(board)
+    }
+}
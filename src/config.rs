@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// User preferences persisted between sessions as JSON in the platform config directory.
+///
+/// Every field has a default so that a missing, partial, or malformed file still loads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub engine_path: Option<PathBuf>,
+    pub move_time_ms: u64,
+    pub skill_level: u8,
+    /// How strongly the engine avoids draws, in centipawns; 0 plays for the objective result.
+    pub contempt: i32,
+    pub multipv: u8,
+    /// Whose point of view scores are shown from, by the option's displayed name.
+    pub eval_perspective: String,
+    pub board_theme: String,
+    /// How pieces are drawn, by the style's displayed name.
+    pub piece_style: String,
+    pub muted: bool,
+    /// Name of the interface theme, as iced displays it.
+    pub theme: String,
+    /// Interface scale on top of the display's own, in percent.
+    pub ui_scale_percent: u16,
+    /// Where the controls sit relative to the board, by the layout's displayed name.
+    pub layout: String,
+    pub turn_chime: bool,
+    pub auto_flip: bool,
+    /// Think time per move when the engine plays itself, slow enough to follow.
+    pub autoplay_move_time_ms: u64,
+    /// Engine options the user changed, by option name, sent before every search.
+    pub engine_options: BTreeMap<String, String>,
+    pub threads: usize,
+    pub hash_mb: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            engine_path: None,
+            move_time_ms: 5000,
+            skill_level: 20,
+            contempt: 0,
+            multipv: 3,
+            eval_perspective: "Side to move".to_string(),
+            board_theme: "Gray".to_string(),
+            piece_style: "Images".to_string(),
+            muted: false,
+            theme: "Light".to_string(),
+            ui_scale_percent: 100,
+            layout: "Panel beside".to_string(),
+            turn_chime: false,
+            auto_flip: false,
+            autoplay_move_time_ms: 1000,
+            engine_options: BTreeMap::new(),
+            // Half the cores keeps the UI and the rest of the machine responsive
+            threads: std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1)),
+            hash_mb: 128,
+        }
+    }
+}
+
+fn config_file(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chess_gui").join(name))
+}
+
+fn write_file(path: Option<PathBuf>, contents: &str) -> Result<(), String> {
+    let path = path.ok_or_else(|| "no config directory on this platform".to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        config_file("config.json")
+    }
+
+    /// Reads the saved config, falling back to defaults when it is absent or unreadable.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_file(Self::path(), &contents)
+    }
+}
+
+/// An unfinished game, saved as it is played so that it survives the app closing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedGame {
+    /// Position the game began from.
+    pub start_fen: String,
+    /// Moves played since in UCI notation, which is how `ChessMove` displays: promotions carry
+    /// their piece letter, as in `e7e8q`.
+    pub moves: Vec<String>,
+    pub human_plays_black: bool,
+    /// Game mode and time control by their displayed names; saves from before these were kept
+    /// read back as empty and fall back to the defaults.
+    #[serde(default)]
+    pub game_mode: String,
+    #[serde(default)]
+    pub time_control: String,
+}
+
+impl SavedGame {
+    fn path() -> Option<PathBuf> {
+        config_file("saved_game.json")
+    }
+
+    /// Reads the saved game, if there is a readable one.
+    pub fn load() -> Option<Self> {
+        let contents = std::fs::read_to_string(Self::path()?).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_file(Self::path(), &contents)
+    }
+
+    /// Forgets the saved game once it has finished or been abandoned.
+    pub fn clear() {
+        if let Some(path) = Self::path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::{ChessMove, Piece, Square};
+    use std::str::FromStr;
+
+    #[test]
+    fn promotion_round_trips_through_saved_game() {
+        let promotion = ChessMove::new(Square::E7, Square::E8, Some(Piece::Queen));
+        let saved = SavedGame {
+            start_fen: "4k3/4P3/8/8/8/8/8/4K3 w - - 0 1".to_string(),
+            moves: vec![promotion.to_string()],
+            human_plays_black: false,
+            game_mode: "Human vs Engine".to_string(),
+            time_control: "5+3".to_string(),
+        };
+        assert_eq!(saved.moves, ["e7e8q"]);
+
+        let json = serde_json::to_string(&saved).unwrap();
+        let loaded: SavedGame = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, saved);
+        assert_eq!(ChessMove::from_str(&loaded.moves[0]).unwrap(), promotion);
+    }
+}
@@ -0,0 +1,93 @@
+use chess::{Board, BoardBuilder, CastleRights, Color, File, Piece, Rank, Square, ALL_SQUARES};
+
+/// A position being assembled square by square before play starts from it.
+pub struct PositionEditor {
+    builder: BoardBuilder,
+    /// Piece placed by clicking a square; `None` erases instead.
+    pub palette: Option<(Piece, Color)>,
+}
+
+impl PositionEditor {
+    pub fn new(board: &Board) -> Self {
+        Self {
+            builder: BoardBuilder::from(board),
+            palette: Some((Piece::Pawn, Color::White)),
+        }
+    }
+
+    pub fn piece_on(&self, square: Square) -> Option<(Piece, Color)> {
+        self.builder[square]
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.builder.get_side_to_move()
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.builder.side_to_move(color);
+    }
+
+    /// Puts the palette piece on `square`, or empties it if that piece is already there.
+    pub fn click(&mut self, square: Square) {
+        self.builder[square] = match self.palette {
+            Some(piece) if self.builder[square] != Some(piece) => Some(piece),
+            _ => None,
+        };
+    }
+
+    pub fn clear(&mut self) {
+        for square in ALL_SQUARES {
+            self.builder[square] = None;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.builder = BoardBuilder::from(Board::default());
+    }
+
+    /// Checks the position and converts it into a playable board.
+    ///
+    /// Castling is allowed wherever king and rook still stand on their original squares;
+    /// en passant is never available in a set-up position.
+    pub fn finish(&self) -> Result<Board, String> {
+        for color in [Color::White, Color::Black] {
+            let kings = ALL_SQUARES.iter()
+                .filter(|&&square| self.builder[square] == Some((Piece::King, color)))
+                .count();
+            if kings != 1 {
+                return Err(format!("{:?} needs exactly one king", color));
+            }
+        }
+        let pawn_on_back_rank = ALL_SQUARES.iter().any(|&square| {
+            matches!(square.get_rank(), Rank::First | Rank::Eighth)
+                && matches!(self.builder[square], Some((Piece::Pawn, _)))
+        });
+        if pawn_on_back_rank {
+            return Err("pawns can't stand on the first or last rank".to_string());
+        }
+
+        let mut builder = self.builder;
+        builder.en_passant(None);
+        for color in [Color::White, Color::Black] {
+            let rights = self.castle_rights(color);
+            builder.castle_rights(color, rights);
+        }
+        Board::try_from(&builder).map_err(|_| "the side not to move is in check".to_string())
+    }
+
+    fn castle_rights(&self, color: Color) -> CastleRights {
+        let rank = color.to_my_backrank();
+        let at = |file| self.builder[Square::make_square(rank, file)];
+        if at(File::E) != Some((Piece::King, color)) {
+            return CastleRights::NoRights;
+        }
+        let kingside = at(File::H) == Some((Piece::Rook, color));
+        let queenside = at(File::A) == Some((Piece::Rook, color));
+        match (kingside, queenside) {
+            (true, true) => CastleRights::Both,
+            (true, false) => CastleRights::KingSide,
+            (false, true) => CastleRights::QueenSide,
+            (false, false) => CastleRights::NoRights,
+        }
+    }
+}
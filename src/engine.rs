@@ -0,0 +1,456 @@
+use chess::{Board, ChessMove, Color};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command as AsyncCommand};
+use tokio::task::JoinHandle;
+
+/// An engine evaluation from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Advantage in centipawns.
+    Cp(i32),
+    /// Forced mate in this many moves; negative when the side to move is getting mated.
+    Mate(i32),
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Cp(cp) => write!(f, "{}", cp),
+            Score::Mate(moves) => write!(f, "Mate in {}", moves),
+        }
+    }
+}
+
+/// Whether a score is exact or only a bound, as reported while a search window fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// One candidate line from the engine: its score and principal variation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineLine {
+    pub score: Score,
+    pub bound: Bound,
+    pub pv: Vec<ChessMove>,
+}
+
+impl EngineLine {
+    /// The score as shown to the user, with ≥ / ≤ marking bounds. `negate` shows it for the
+    /// side not to move, which also turns a lower bound into an upper one.
+    pub fn score_text(&self, negate: bool) -> String {
+        let prefix = match (self.bound, negate) {
+            (Bound::Exact, _) => "",
+            (Bound::Lower, false) | (Bound::Upper, true) => "≥",
+            (Bound::Upper, false) | (Bound::Lower, true) => "≤",
+        };
+        let score = match (self.score, negate) {
+            (score, false) => score,
+            (Score::Cp(cp), true) => Score::Cp(-cp),
+            (Score::Mate(moves), true) => Score::Mate(-moves),
+        };
+        format!("{}{}", prefix, score)
+    }
+}
+
+/// How far the engine has searched, as last reported in its `info` lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub depth: u32,
+    pub nodes: u64,
+    pub nps: u64,
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "depth {}, {}nodes, {}nps", self.depth, format_count(self.nodes), format_count(self.nps))
+    }
+}
+
+/// Abbreviates a large count with a metric prefix, e.g. 3_100_000 as "3.1 M".
+fn format_count(count: u64) -> String {
+    match count {
+        0..=999 => format!("{} ", count),
+        1_000..=999_999 => format!("{:.1} k", count as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.1} M", count as f64 / 1e6),
+        _ => format!("{:.1} G", count as f64 / 1e9),
+    }
+}
+
+/// The position handed to the engine, along with the moves that led to it when they are known.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchPosition {
+    pub board: Board,
+    /// Moves from the standard starting position; empty for games set up from a FEN.
+    moves: Vec<ChessMove>,
+}
+
+impl SearchPosition {
+    /// Keeps the moves only when `start` is the standard position, since `startpos` can't
+    /// describe any other.
+    pub fn new(start: &Board, moves: &[ChessMove], board: Board) -> Self {
+        let moves = if *start == Board::default() { moves.to_vec() } else { Vec::new() };
+        Self { board, moves }
+    }
+
+    /// The UCI `position` command. Sending the moves lets the engine see the game's history,
+    /// and with it repetitions; without them it gets a bare FEN.
+    pub fn command(&self) -> String {
+        if self.moves.is_empty() && self.board != Board::default() {
+            return format!("position fen {}\n", self.board);
+        }
+        let mut command = "position startpos".to_string();
+        if !self.moves.is_empty() {
+            command.push_str(" moves");
+            for mv in &self.moves {
+                command.push_str(&format!(" {}", mv));
+            }
+        }
+        command.push('\n');
+        command
+    }
+}
+
+/// How long the engine may think about a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchLimit {
+    /// A fixed think time in milliseconds.
+    MoveTime(u64),
+    /// Both clocks and increments in milliseconds, leaving time management to the engine.
+    Clock { wtime: u64, btime: u64, winc: u64, binc: u64 },
+}
+
+impl SearchLimit {
+    /// How long to wait for `bestmove` before giving up on the engine: twice the time it may
+    /// spend on the move, so an engine that merely overruns isn't cut off, plus some slack.
+    pub fn time_budget(&self, side: Color) -> Duration {
+        let millis = match *self {
+            SearchLimit::MoveTime(ms) => ms,
+            SearchLimit::Clock { wtime, btime, winc, binc } => match side {
+                Color::White => wtime + winc,
+                Color::Black => btime + binc,
+            },
+        };
+        Duration::from_millis(millis) * 2 + Duration::from_secs(2)
+    }
+
+    pub fn go_command(&self) -> String {
+        match self {
+            SearchLimit::MoveTime(ms) => format!("go movetime {}\n", ms),
+            SearchLimit::Clock { wtime, btime, winc, binc } => {
+                format!("go wtime {} btime {} winc {} binc {}\n", wtime, btime, winc, binc)
+            }
+        }
+    }
+}
+
+/// What the engine has reported about a search so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineReport {
+    /// Set once the search has finished; `None` when the position has no legal move.
+    pub best_move: Option<ChessMove>,
+    /// Candidate lines in MultiPV order; the first is the engine's main line.
+    pub lines: Vec<EngineLine>,
+    pub stats: SearchStats,
+    /// Name from the engine's `id name` line.
+    pub engine_name: Option<String>,
+}
+
+impl EngineReport {
+    /// Records the move from a `bestmove` line, returning whether `line` was one.
+    ///
+    /// `bestmove (none)` or `bestmove 0000` means there is no legal move to play.
+    pub fn record_best_move(&mut self, line: &str) -> bool {
+        let Some(rest) = line.strip_prefix("bestmove") else {
+            return false;
+        };
+        self.best_move = rest.split_whitespace()
+            .next()
+            .and_then(|m| ChessMove::from_str(m).ok());
+        true
+    }
+
+    /// Folds an `info` line into the report, returning whether it updated a candidate line.
+    pub fn record_info(&mut self, line: &str) -> bool {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let value_after = |token: &str| {
+            parts.iter().position(|&s| s == token)
+                .and_then(|idx| parts.get(idx + 1))
+        };
+        if let Some(depth) = value_after("depth").and_then(|n| n.parse().ok()) {
+            self.stats.depth = depth;
+        }
+        if let Some(nodes) = value_after("nodes").and_then(|n| n.parse().ok()) {
+            self.stats.nodes = nodes;
+        }
+        if let Some(nps) = value_after("nps").and_then(|n| n.parse().ok()) {
+            self.stats.nps = nps;
+        }
+
+        // Only lines carrying a scored PV describe a candidate; currmove updates etc. don't
+        let Some(pv_idx) = parts.iter().position(|&s| s == "pv") else {
+            return false;
+        };
+        let score = if let Some(cp) = value_after("cp").and_then(|n| n.parse().ok()) {
+            Score::Cp(cp)
+        } else if let Some(moves) = value_after("mate").and_then(|n| n.parse().ok()) {
+            Score::Mate(moves)
+        } else {
+            return false;
+        };
+        let bound = if parts.contains(&"lowerbound") {
+            Bound::Lower
+        } else if parts.contains(&"upperbound") {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        let pv = parts[pv_idx + 1..]
+            .iter()
+            .filter_map(|m| ChessMove::from_str(m).ok())
+            .collect();
+
+        let index = value_after("multipv")
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&n| n >= 1)
+            .unwrap_or(1);
+        let line = EngineLine { score, bound, pv };
+        // MultiPV lines arrive in order, so a line is either known already or the next one
+        if index <= self.lines.len() {
+            self.lines[index - 1] = line;
+        } else if index == self.lines.len() + 1 {
+            self.lines.push(line);
+        }
+        true
+    }
+}
+
+/// The kind of value an engine option takes, as declared by its `type` token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionKind {
+    Check,
+    Spin { min: i64, max: i64 },
+    Combo(Vec<String>),
+    /// A command rather than a setting; it carries no value.
+    Button,
+    String,
+}
+
+/// One `option` line from the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+    pub default: String,
+}
+
+impl EngineOption {
+    /// Parses the part of an `option` line after `option name `.
+    fn parse(rest: &str) -> Option<Self> {
+        // Names and values may contain spaces, so each runs up to the next keyword
+        let (name, declaration) = rest.split_once(" type ")?;
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        let mut tokens = declaration.split_whitespace();
+        let kind = tokens.next()?;
+        for token in tokens {
+            match (token, fields.last_mut()) {
+                ("default" | "min" | "max" | "var", _) => fields.push((token, String::new())),
+                (word, Some((_, value))) => {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(word);
+                }
+                (_, None) => {}
+            }
+        }
+        let field = |key: &str| fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone());
+        let kind = match kind {
+            "check" => OptionKind::Check,
+            "spin" => OptionKind::Spin {
+                min: field("min")?.parse().ok()?,
+                max: field("max")?.parse().ok()?,
+            },
+            "combo" => OptionKind::Combo(
+                fields.iter().filter(|(k, _)| *k == "var").map(|(_, v)| v.clone()).collect(),
+            ),
+            "button" => OptionKind::Button,
+            "string" => OptionKind::String,
+            _ => return None,
+        };
+        // Stockfish spells an empty string default as `<empty>`
+        let default = field("default").filter(|v| v != "<empty>").unwrap_or_default();
+        Some(Self { name: name.trim().to_string(), kind, default })
+    }
+}
+
+/// What an engine advertises about itself between `uci` and `uciok`.
+#[derive(Debug, Clone, Default)]
+pub struct EngineProfile {
+    pub name: Option<String>,
+    pub options: Vec<EngineOption>,
+}
+
+impl EngineProfile {
+    fn parse_line(&mut self, line: &str) {
+        if let Some(name) = line.strip_prefix("id name ") {
+            self.name = Some(name.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("option name ") {
+            self.options.extend(EngineOption::parse(rest));
+        }
+    }
+
+    /// UCI option names are case-insensitive.
+    fn supports(&self, option: &str) -> bool {
+        self.options.iter().any(|known| known.name.eq_ignore_ascii_case(option))
+    }
+}
+
+/// Starts the engine just long enough to learn what it advertises.
+pub async fn probe(path: PathBuf) -> Result<EngineProfile, String> {
+    let mut session = EngineSession::start(&path).await?;
+    let profile = std::mem::take(&mut session.profile);
+    session.quit().await;
+    Ok(profile)
+}
+
+/// How long an engine gets to answer `uci` with `uciok`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running engine process that has completed the `uci` handshake.
+///
+/// Dropping it kills the process, so an abandoned search or analysis never lingers.
+pub struct EngineSession {
+    child: Child,
+    stdin: ChildStdin,
+    reader: BufReader<ChildStdout>,
+    raw_line: Vec<u8>,
+    /// Collects everything the engine writes to stderr, so the pipe never fills up and
+    /// blocks it.
+    stderr: Option<JoinHandle<String>>,
+    pub profile: EngineProfile,
+}
+
+impl EngineSession {
+    pub async fn start(path: &Path) -> Result<Self, String> {
+        let mut child = AsyncCommand::new(path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {}", path.display(), e))?;
+
+        // Keep stdin open for the whole session: Stockfish treats EOF as `quit` and would
+        // abandon a search immediately
+        let stdin = child.stdin.take()
+            .ok_or_else(|| "engine stdin unavailable".to_string())?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| "engine stdout unavailable".to_string())?;
+        let stderr = child.stderr.take().map(|stderr| tokio::spawn(collect_stderr(stderr)));
+        let mut session = Self {
+            child,
+            stdin,
+            reader: BufReader::new(stdout),
+            raw_line: Vec::new(),
+            stderr,
+            profile: EngineProfile::default(),
+        };
+
+        // Learn the engine's name and options before configuring it. Something that isn't a
+        // UCI engine may never answer, so the handshake is bounded.
+        if session.send("uci\n").await.is_err() {
+            // A program that quits at once closes its input too, so a failed write is the same verdict
+            return Err(session.exit_error("not a UCI engine: it exited before uciok").await);
+        }
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, session.handshake()).await {
+            Ok(Ok(())) => Ok(session),
+            Ok(Err(error)) => Err(error),
+            Err(_) => {
+                session.quit().await;
+                Err(format!("not a UCI engine: no uciok within {} s", HANDSHAKE_TIMEOUT.as_secs()))
+            }
+        }
+    }
+
+    async fn handshake(&mut self) -> Result<(), String> {
+        loop {
+            let Some(line) = self.read_line().await? else {
+                return Err(self.exit_error("not a UCI engine: it exited before uciok").await);
+            };
+            if line == "uciok" {
+                return Ok(());
+            }
+            self.profile.parse_line(&line);
+        }
+    }
+
+    pub async fn send(&mut self, commands: &str) -> Result<(), String> {
+        self.stdin.write_all(commands.as_bytes()).await
+            .map_err(|e| format!("write failed: {}", e))?;
+        self.stdin.flush().await
+            .map_err(|e| format!("flush failed: {}", e))
+    }
+
+    /// Reads one complete line from the engine, or `None` once it has exited.
+    ///
+    /// A line split across reads stays buffered until its newline arrives, so a read that is
+    /// cancelled, as by `select!`, loses nothing and the next call carries on with it.
+    pub async fn read_line(&mut self) -> Result<Option<String>, String> {
+        let n = self.reader.read_until(b'\n', &mut self.raw_line).await
+            .map_err(|e| format!("read failed: {}", e))?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let line = String::from_utf8_lossy(&self.raw_line).trim_end().to_string();
+        self.raw_line.clear();
+        Ok(Some(line))
+    }
+
+    /// Builds `setoption` commands for the options the engine advertised; other engines
+    /// may reject ones they don't know.
+    pub fn option_commands(&self, options: &[(&str, String)]) -> String {
+        options.iter()
+            .filter(|(name, _)| self.profile.supports(name))
+            .map(|(name, value)| format!("setoption name {} value {}\n", name, value))
+            .collect()
+    }
+
+    /// Describes an unexpected exit, including whatever the engine wrote to stderr.
+    ///
+    /// Call once stdout has closed; waits briefly for the process to finish writing.
+    pub async fn exit_error(&mut self, what: &str) -> String {
+        let stderr = match self.stderr.take() {
+            Some(task) => {
+                let _ = tokio::time::timeout(Duration::from_millis(500), self.child.wait()).await;
+                match tokio::time::timeout(Duration::from_millis(500), task).await {
+                    Ok(Ok(text)) => text,
+                    _ => String::new(),
+                }
+            }
+            None => String::new(),
+        };
+        match stderr.trim() {
+            "" => what.to_string(),
+            stderr => format!("{}: {}", what, stderr),
+        }
+    }
+
+    /// Asks the engine to exit and reaps it.
+    pub async fn quit(mut self) {
+        let _ = self.stdin.write_all(b"quit\n").await;
+        let _ = self.child.kill().await;
+    }
+}
+
+async fn collect_stderr(mut stderr: ChildStderr) -> String {
+    let mut raw = Vec::new();
+    let _ = stderr.read_to_end(&mut raw).await;
+    String::from_utf8_lossy(&raw).into_owned()
+}
@@ -0,0 +1,52 @@
+use iced::mouse;
+use iced::widget::canvas::{self, Frame, Geometry, Path, Stroke};
+use iced::{Color, Point, Rectangle, Renderer, Theme};
+
+/// Evaluations beyond this many centipawns are drawn at the graph's edge.
+const LIMIT_CP: i32 = 500;
+
+/// Line chart of White's evaluation across the game, for spotting where it swung.
+pub struct EvalGraph<'a> {
+    /// (ply, centipawns from White's point of view), in ply order.
+    pub points: &'a [(usize, i32)],
+    /// Plies played so far, which the x-axis spans.
+    pub plies: usize,
+}
+
+impl<Message> canvas::Program<Message> for EvalGraph<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let (width, height) = (bounds.width, bounds.height);
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color::from_rgb(0.15, 0.15, 0.15));
+
+        // White's advantage rises above the middle line, Black's falls below it
+        let x = |ply: usize| ply as f32 / self.plies.max(1) as f32 * width;
+        let y = |cp: i32| height / 2.0 * (1.0 - cp.clamp(-LIMIT_CP, LIMIT_CP) as f32 / LIMIT_CP as f32);
+        frame.stroke(
+            &Path::line(Point::new(0.0, height / 2.0), Point::new(width, height / 2.0)),
+            Stroke::default().with_color(Color::from_rgb(0.5, 0.5, 0.5)).with_width(1.0),
+        );
+        let line = Path::new(|builder| {
+            for (index, &(ply, cp)) in self.points.iter().enumerate() {
+                let point = Point::new(x(ply), y(cp));
+                if index == 0 {
+                    builder.move_to(point);
+                } else {
+                    builder.line_to(point);
+                }
+            }
+        });
+        frame.stroke(&line, Stroke::default().with_color(Color::from_rgb(0.95, 0.95, 0.95)).with_width(2.0));
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -1,42 +1,276 @@
+mod board_view;
+mod config;
+mod editor;
+mod engine;
+mod eval_graph;
+mod openings;
+mod sound;
+
+use board_view::{Banner, BoardView, PieceArt, SquareView, PIECE_PADDING};
+use config::{Config, SavedGame};
+use editor::PositionEditor;
+use eval_graph::EvalGraph;
+use engine::{EngineLine, EngineOption, EngineProfile, EngineReport, EngineSession, OptionKind, Score, SearchLimit, SearchPosition, SearchStats};
+use openings::BookStatus;
+use sound::{SoundEvent, Sounds};
 use iced::{
-    alignment, executor, font, Alignment, Application, Command, Element, Length,
-    Settings, Theme, Color,
-    widget::{Button, Column, Container, Row, Text}
+    alignment, clipboard, event, executor, keyboard, subscription, time, window, Alignment, Application, Command, Element,
+    Event, Font, Length, Settings, Subscription, Theme, Color,
+    widget::{svg, Button, Canvas, Checkbox, Column, Container, PickList, ProgressBar, Radio, Row, Scrollable, Slider, Space, Svg, Text, TextInput}
 };
-use chess::{Board, ChessMove, Color as ChessColor, File, Game, GameResult, Piece, Rank, Square};
+use chess::{Board, BoardStatus, ChessMove, MoveGen, Color as ChessColor, File, Game, GameResult, Piece, Rank, Square, ALL_SQUARES};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
-use tokio::process::Command as AsyncCommand;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-
-struct UciMove(pub ChessMove);
-
-impl fmt::Display for UciMove {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
+use std::time::{Duration, Instant};
+use iced::futures::future::{self, AbortHandle, AbortRegistration, Abortable};
+use iced::futures::stream::{self, StreamExt};
+use iced::futures::channel::{mpsc, oneshot};
+use iced::futures::SinkExt;
 
 fn main() -> iced::Result {
     ChessApp::run(Settings::default())
 }
 
+
 struct ChessApp {
     game: Game,
+    history: Vec<Game>,
+    move_history: Vec<ChessMove>,
+    san_history: Vec<String>,
+    /// Both clocks, White's first, as they stood when each move was played and before its
+    /// increment, so taking moves back also takes back the increments they earned.
+    clock_history: Vec<(Duration, Duration)>,
+    /// Moves taken back by Undo, the next one to replay on top.
+    redo_stack: Vec<ChessMove>,
     selected_square: Option<Square>,
+    /// Move queued while the engine is thinking, tried as soon as its reply lands.
+    premove: Option<(Square, Square)>,
+    /// Piece picked up for a premove whose destination hasn't been clicked yet.
+    premove_source: Option<Square>,
+    cursor_square: Square,
+    hovered_square: Option<Square>,
     stockfish_path: PathBuf,
+    /// Name the engine reported in its `id name` line, once it has been heard from.
+    engine_name: Option<String>,
+    engine_path_input: String,
+    fen_input: String,
+    /// Move being typed in SAN or UCI notation.
+    move_input: String,
+    /// File letter typed on the board so far, waiting for its rank to name a square.
+    square_input: String,
+    move_time_ms: u64,
+    skill_level: u8,
+    contempt: i32,
     current_turn: ChessColor,
-    status: String,
-    engine_evaluation: String,
-    principal_variation: Vec<ChessMove>,
+    player_color: ChessColor,
+    /// The side chosen for the human, taken up when the next game starts.
+    next_player_color: ChessColor,
+    flipped: bool,
+    square_size: f32,
+    board_theme: BoardTheme,
+    piece_style: PieceStyle,
+    /// A passing note, such as what was just done, shown ahead of the status
+    /// derived from the game; the next move clears it.
+    status: Option<String>,
+    /// Something that went wrong, shown in a banner across the top until dismissed.
+    error: Option<String>,
+    engine_lines: Vec<EngineLine>,
+    eval_cp: Option<i32>,
+    /// White-relative engine evaluations, keyed by the ply of the position they describe.
+    eval_history: Vec<(usize, i32)>,
+    /// Depth and node counts from the engine's latest report.
+    search_stats: Option<SearchStats>,
+    analysis_position: Board,
+    eval_perspective: EvalPerspective,
+    /// The engine's latest change of mind about the best move in this position, as (new, was).
+    best_change: Option<(ChessMove, ChessMove)>,
+    multipv: u8,
+    promotion_pending: Option<PromotionPending>,
+    draw_offer: Option<ChessColor>,
+    draw_claimable: bool,
+    time_control: TimeControl,
+    white_time: Duration,
+    black_time: Duration,
+    last_tick: Option<Instant>,
+    flagged: Option<ChessColor>,
+    /// Set when the game was stopped because neither side can mate; the `chess` crate has no
+    /// such result, so it is recorded as an agreed draw.
+    insufficient_material: bool,
+    /// In timed games, send the clocks to the engine instead of a fixed think time.
+    engine_uses_clock: bool,
+    sounds: Option<Sounds>,
+    muted: bool,
+    theme: Theme,
+    ui_scale: UiScale,
+    layout: LayoutMode,
+    /// Last window size reported, in unscaled logical pixels, to refit the board on rescaling.
+    window_size: Option<(f32, f32)>,
+    /// Chime when the engine has moved, for a player who looked away during a long think.
+    turn_chime: bool,
+    window_focused: bool,
+    /// Shows the draw-rule debugging panel.
+    debug_view: bool,
+    engine_task: Option<AbortHandle>,
+    /// Bumped whenever a search is abandoned, which starting a new one also does; replies
+    /// tagged with an older id come from a search that no longer applies and are dropped.
+    search_id: u64,
+    /// Tells the engine searching for a move to stop and play the best it has found.
+    force_move: Option<oneshot::Sender<()>>,
+    /// Whether the engine is analysing the current position without playing a move.
+    analyzing: bool,
+    /// Analysis-only layout: play controls are hidden, the engine analyses whatever position
+    /// is on the board, and the board only moves through navigation and setup.
+    analysis_layout: bool,
+    /// Set after jumping through the move list: whichever side is to move there may be moved
+    /// by hand, and sides and the engine stay as they were until a move is played.
+    reviewing: bool,
+    /// Animation step of the thinking indicator shown while awaiting the engine.
+    thinking_frame: usize,
+    /// When the engine's move search began and the think time it was given; `None` while it
+    /// manages its own clock, as nothing then says how long it will take.
+    think_budget: Option<(Instant, Duration)>,
+    /// Short-lived confirmation shown in place of the status, with the id of its expiry timer.
+    notice: Option<(u64, String)>,
+    /// Set while a position is being set up instead of played.
+    editor: Option<PositionEditor>,
+    game_mode: GameMode,
+    /// In two-player mode, turn the board so the side to move is always at the bottom.
+    auto_flip: bool,
+    /// In engine-vs-engine mode, whether the engine keeps replying to itself.
+    autoplaying: bool,
+    autoplay_move_time_ms: u64,
+    /// Game left unfinished last session, until the player decides whether to resume it.
+    resume_offer: Option<SavedGame>,
+    /// What was last written to disk, so unchanged state isn't rewritten.
+    last_saved: Option<SavedGame>,
+    /// Whether the player has put away the game-over banner to look at the final position.
+    game_over_dismissed: bool,
+    /// Whether "New Game" is waiting for the player to confirm discarding the game in progress.
+    confirming_new_game: bool,
+    /// Study annotations drawn with the right mouse button; cleared whenever a move is made.
+    arrows: Vec<(Square, Square)>,
+    marked_squares: Vec<Square>,
+    /// Square where the right button went down, until it is released.
+    annotation_start: Option<Square>,
+    /// Options the engine advertised, learned by probing it at startup and on a path change.
+    advertised_options: Vec<EngineOption>,
+    /// Values the user picked for engine options, sent before every search.
+    engine_options: BTreeMap<String, String>,
+    show_engine_options: bool,
+    /// Search threads the engine may use.
+    threads: usize,
+    /// Transposition table size in megabytes.
+    hash_mb: usize,
+    hash_input: String,
+    /// Engine suggestion for the human's move, shown until a move is made.
+    hint: Option<ChessMove>,
+    hint_pending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PromotionPending {
+    from: Square,
+    to: Square,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     SquareSelected(Square),
-    EngineMove((ChessMove, String, Vec<ChessMove>)),
+    PromotionChosen(Piece),
+    ForceMove,
+    ClearError,
+    /// Jumps to the position after this many moves, through the undo and redo stacks.
+    GotoMove(usize),
+    /// The outcome of a move search, tagged with its search id.
+    EngineMove(u64, Result<EngineReport, String>),
+    EngineError(String),
+    EnginePathInputChanged(String),
+    SetEnginePath(PathBuf),
+    SetMoveTime(u64),
+    SetSkill(u8),
+    SetContempt(i32),
+    SetDifficulty(Difficulty),
+    SetMultiPv(u8),
+    SetThreads(usize),
+    HashInputChanged(String),
+    SetHash,
+    SetPlayerColor(ChessColor),
     NewGame,
+    SetGameMode(GameMode),
+    SetAutoFlip(bool),
+    SetAutoplay(bool),
+    SetAutoplayMoveTime(u64),
+    ConfirmNewGame,
+    DismissGameOver,
+    ResumeGame,
+    DeclineResume,
+    CancelNewGame,
+    FenInputChanged(String),
+    MoveInputChanged(String),
+    TextMove(String),
+    LoadFen,
+    EditPosition,
+    EditSquare(Square),
+    SetPalette(Option<(Piece, ChessColor)>),
+    SetEditSideToMove(ChessColor),
+    ClearEditBoard,
+    ResetEditBoard,
+    FinishEdit,
+    CancelEdit,
+    Undo,
+    Redo,
+    FlipBoard,
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    ClaimDraw,
+    CopyFen,
+    CopyPgn,
+    NoticeExpired(u64),
+    ExportPgn,
+    WindowResized(u32, u32),
+    SetBoardTheme(BoardTheme),
+    SetEvalPerspective(EvalPerspective),
+    SetPieceStyle(PieceStyle),
+    SetTimeControl(TimeControl),
+    SetEngineUsesClock(bool),
+    Tick(Instant),
+    SetMuted(bool),
+    ToggleTheme,
+    SetTheme(Theme),
+    SetUiScale(UiScale),
+    SetLayout(LayoutMode),
+    SetTurnChime(bool),
+    WindowFocused(bool),
+    SetDebugView(bool),
+    /// Moves the keyboard cursor by (right, up) squares as seen on screen.
+    MoveCursor(i8, i8),
+    SelectCursor,
+    /// A file letter or rank digit typed to name a square, as in "e2" then "e4".
+    SquareKey(char),
+    EngineProbed(Result<EngineProfile, String>),
+    SetEngineOption(String, String),
+    ResetEngineOptions,
+    SetShowEngineOptions(bool),
+    AnnotationStart(Square),
+    AnnotationEnd(Square),
+    ClearAnnotations,
+    SquareHovered(Square),
+    SquareUnhovered(Square),
+    RequestHint,
+    Hint(Board, ChessMove),
+    HintFailed(String),
+    /// The quick evaluation of the position at a ply, for judging the move played from it.
+    PositionChecked(usize, Board, Option<Score>),
+    StartAnalysis,
+    ToggleLayout,
+    StopAnalysis,
+    AnalysisUpdate(Board, EngineReport),
+    /// Interim lines from a search that is still running, tagged like `EngineMove`.
+    EngineProgress(u64, EngineReport),
+    ThinkingTick,
 }
 
 impl Application for ChessApp {
@@ -47,19 +281,118 @@ impl Application for ChessApp {
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
         let game = Game::new();
-        let stockfish_path = PathBuf::from("/usr/local/bin/stockfish");
-        
+        let config = Config::load();
+        let stockfish_path = resolve_stockfish_path(config.engine_path);
+        let stockfish_path_for_probe = stockfish_path.clone();
+        let board_theme = BoardTheme::ALL.iter()
+            .find(|theme| theme.name == config.board_theme)
+            .copied()
+            .unwrap_or(BoardTheme::GRAY);
+
         (
             Self {
                 game,
+                history: Vec::new(),
+                move_history: Vec::new(),
+                san_history: Vec::new(),
+                clock_history: Vec::new(),
+                redo_stack: Vec::new(),
                 selected_square: None,
+                premove: None,
+                premove_source: None,
+                cursor_square: Square::E2,
+                hovered_square: None,
+                engine_path_input: stockfish_path.display().to_string(),
+                fen_input: String::new(),
+                move_input: String::new(),
+                square_input: String::new(),
                 stockfish_path,
+                engine_name: None,
+                move_time_ms: config.move_time_ms.clamp(100, 30_000),
+                skill_level: config.skill_level.min(20),
+                contempt: config.contempt.clamp(-100, 100),
                 current_turn: ChessColor::White,
-                status: "White's turn".to_string(),
-                engine_evaluation: String::new(),
-                principal_variation: Vec::new(),
+                player_color: ChessColor::White,
+                next_player_color: ChessColor::White,
+                flipped: false,
+                square_size: DEFAULT_SQUARE_SIZE,
+                board_theme,
+                piece_style: PieceStyle::ALL.iter()
+                    .copied()
+                    .find(|style| style.to_string() == config.piece_style)
+                    .unwrap_or(PieceStyle::Images),
+                status: None,
+                error: None,
+                engine_lines: Vec::new(),
+                eval_cp: None,
+                eval_history: Vec::new(),
+                search_stats: None,
+                analysis_position: Board::default(),
+                eval_perspective: EvalPerspective::ALL.iter()
+                    .copied()
+                    .find(|perspective| perspective.to_string() == config.eval_perspective)
+                    .unwrap_or(EvalPerspective::SideToMove),
+                best_change: None,
+                multipv: config.multipv.clamp(1, 5),
+                promotion_pending: None,
+                draw_offer: None,
+                draw_claimable: false,
+                time_control: TimeControl::UNTIMED,
+                white_time: Duration::ZERO,
+                black_time: Duration::ZERO,
+                last_tick: None,
+                flagged: None,
+                insufficient_material: false,
+                engine_uses_clock: true,
+                sounds: Sounds::new(),
+                muted: config.muted,
+                theme: Theme::ALL.iter()
+                    .find(|theme| theme.to_string() == config.theme)
+                    .cloned()
+                    .unwrap_or(Theme::Light),
+                ui_scale: UiScale::ALL.iter()
+                    .copied()
+                    .find(|scale| scale.0 == config.ui_scale_percent)
+                    .unwrap_or(UiScale(100)),
+                layout: LayoutMode::ALL.iter()
+                    .copied()
+                    .find(|layout| layout.to_string() == config.layout)
+                    .unwrap_or(LayoutMode::Side),
+                window_size: None,
+                turn_chime: config.turn_chime,
+                window_focused: true,
+                debug_view: false,
+                engine_task: None,
+                search_id: 0,
+                analysis_layout: false,
+                reviewing: false,
+                force_move: None,
+                analyzing: false,
+                thinking_frame: 0,
+                think_budget: None,
+                notice: None,
+                editor: None,
+                game_mode: GameMode::HumanVsEngine,
+                auto_flip: config.auto_flip,
+                autoplaying: false,
+                autoplay_move_time_ms: config.autoplay_move_time_ms.clamp(100, 30_000),
+                resume_offer: SavedGame::load(),
+                last_saved: None,
+                confirming_new_game: false,
+                game_over_dismissed: false,
+                arrows: Vec::new(),
+                marked_squares: Vec::new(),
+                annotation_start: None,
+                advertised_options: Vec::new(),
+                engine_options: config.engine_options,
+                show_engine_options: false,
+                threads: config.threads.clamp(1, max_threads()),
+                hash_mb: config.hash_mb.max(1),
+                hash_input: config.hash_mb.max(1).to_string(),
+                hint: None,
+                hint_pending: false,
             },
-            Command::none(),
+            Command::perform(engine::probe(stockfish_path_for_probe), Message::EngineProbed),
         )
     }
 
@@ -67,280 +400,3360 @@ impl Application for ChessApp {
         String::from("Rust Chess - Stockfish")
     }
 
+    fn theme(&self) -> Theme {
+        self.theme.clone()
+    }
+
+    /// iced already renders in logical pixels, so this only applies the user's own scale on
+    /// top of the display's.
+    fn scale_factor(&self) -> f64 {
+        f64::from(self.ui_scale.0) / 100.0
+    }
+
     fn update(&mut self, message: Message) -> Command<Message> {
-        if self.game.result().is_some() {
-            return Command::none();
-        }
+        let command = self.handle_message(message);
+        // The error banner comes and goes with many messages and takes room from the board
+        self.fit_board();
+        self.autosave();
+        command
+    }
 
-        match message {
-            Message::SquareSelected(square) => {
-                if self.current_turn == ChessColor::White {
-                    if let Some(selected) = self.selected_square {
-                        let mv = ChessMove::new(selected, square, None);
-                        
-                        if self.game.current_position().legal(mv) {
-                            let mut new_game = self.game.clone();
-                            if new_game.make_move(mv) {
-                                self.game = new_game;
-                                self.current_turn = ChessColor::Black;
-                                self.status = "Stockfish is thinking...".to_string();
-                                self.selected_square = None;
-                                return get_stockfish_move(
-                                    self.stockfish_path.clone(),
-                                    self.game.clone()
-                                );
-                            }
-                        }
-                    }
-                    self.selected_square = Some(square);
-                }
-                Command::none()
-            }
-            Message::EngineMove((mv, eval, pv)) => {
-                let mut new_game = self.game.clone();
-                if new_game.make_move(mv) {
-                    self.game = new_game;
-                    self.current_turn = ChessColor::White;
-                    self.status = "White's turn".to_string();
-                    self.engine_evaluation = eval;
-                    self.principal_variation = pv;
-                }
-                Command::none()
+    /// Everything that updates the app without a click: window and keyboard events, the clock
+    /// tick, the thinking indicator, and streamed analysis. Each time-based source runs only
+    /// while something needs it.
+    fn subscription(&self) -> Subscription<Message> {
+        let events = event::listen_with(|event, status| match event {
+            Event::Window(_, window::Event::Resized { width, height }) => {
+                Some(Message::WindowResized(width, height))
             }
-            Message::NewGame => {
-                self.game = Game::new();
-                self.current_turn = ChessColor::White;
-                self.selected_square = None;
-                self.status = "New game - White's turn".to_string();
-                self.engine_evaluation.clear();
-                self.principal_variation.clear();
-                Command::none()
+            Event::Window(_, window::Event::Focused) => Some(Message::WindowFocused(true)),
+            Event::Window(_, window::Event::Unfocused) => Some(Message::WindowFocused(false)),
+            // Keys already handled by a widget, such as the engine path input, are left alone
+            Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. })
+                if status == event::Status::Ignored =>
+            {
+                shortcut(key, modifiers)
             }
+            _ => None,
+        });
+
+        let mut subscriptions = vec![events];
+
+        // Clocks start with the first move and stop once the game is decided, or while the game
+        // is being studied instead of played
+        let clocks_running = self.time_control.is_timed()
+            && self.editor.is_none()
+            && !self.analysis_layout
+            && !self.history.is_empty()
+            && self.game.result().is_none();
+        if clocks_running {
+            subscriptions.push(time::every(Duration::from_millis(100)).map(Message::Tick));
+        }
+        if self.engine_task.is_some() {
+            subscriptions.push(time::every(THINKING_TICK).map(|_| Message::ThinkingTick));
+        }
+        if self.analysis_running() {
+            subscriptions.push(analysis_subscription(
+                self.stockfish_path.clone(),
+                self.search_position(),
+                self.multipv,
+                self.session_options(),
+            ));
         }
+        Subscription::batch(subscriptions)
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let board = self.game.current_position();
-        let status = match self.game.result() {
-            Some(GameResult::WhiteCheckmates) => "White wins by checkmate!",
-            Some(GameResult::BlackCheckmates) => "Black wins by checkmate!",
-            Some(GameResult::Stalemate) => "Draw by stalemate",
-            Some(GameResult::DrawAccepted) => "Draw accepted",
-            Some(GameResult::WhiteResigns) => "White resigns. Black wins!",
-            Some(GameResult::BlackResigns) => "Black resigns. White wins!",
-            Some(GameResult::DrawDeclared) => "Draw declared",
-            None => &self.status,
-        };
-
-        let mut rows = Column::new().spacing(5);
-        
-        // Proper board orientation (White at bottom)
-        for rank in (0..8).rev() {
-            let mut row = Row::new().spacing(5);
-            
-            for file in 0..8 {
-                let square = Square::make_square(
-                    Rank::from_index(rank),
-                    File::from_index(file)
-                );
-                let piece = board.piece_on(square);
-                let color = board.color_on(square).unwrap_or(ChessColor::White);
-                let is_light_square = (file + rank) % 2 == 0;
-                
-                // Square colors
-                let button_color = if self.selected_square == Some(square) {
-                    Color::from_rgb(0.7, 0.7, 0.0) // Yellow for selected
-                } else if is_light_square {
-                    Color::from_rgb(0.73, 0.73, 0.73) // Light squares
-                } else {
-                    Color::from_rgb(0.25, 0.25, 0.25) // Dark squares
-                };
+        let status = self.status_text();
+        // Cycling dots show the app is still alive during a long search
+        let status_line = if let Some((_, notice)) = &self.notice {
+            notice.clone()
+        } else if self.engine_task.is_some() && self.game.result().is_none() {
+            format!("{}{}", status, ".".repeat(self.thinking_frame / THINKING_TICKS_PER_DOT + 1))
+        } else {
+            status
+        };
 
-                // Piece colors with clear contrast
-                let (text_color, symbol) = match color {
-                    ChessColor::White => (
-                        Color::from_rgb(0.95, 0.95, 0.95), // Bright white
-                        white_piece_symbol(piece)
-                    ),
-                    ChessColor::Black => (
-                        Color::from_rgb(0.1, 0.1, 0.1),    // Dark black
-                        black_piece_symbol(piece)
-                    ),
-                };
+        // Destinations the selected piece can legally reach (MoveGen accounts for pins and checks)
+        let legal_targets: HashSet<Square> = match self.selected_square {
+            Some(selected) => MoveGen::new_legal(&board)
+                .filter(|mv| mv.get_source() == selected)
+                .map(|mv| mv.get_dest())
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        // Whether moving the selected piece to the hovered square would be legal. Own pieces are
+        // left alone, as clicking one picks it up instead
+        let hover_preview = match (self.selected_square, self.hovered_square) {
+            (Some(from), Some(to)) if self.is_human_turn() && board.color_on(to) != Some(board.side_to_move()) => {
+                Some((to, is_promotion(&board, from, to) || board.legal(ChessMove::new(from, to, None))))
+            }
+            _ => None,
+        };
+
+        // A pawn that can be taken en passant right now; the chess crate reports the pawn's own
+        // square, and the capture lands on the square it skipped
+        let en_passant = board.en_passant().filter(|&pawn| {
+            let target = pawn.forward(board.side_to_move());
+            self.editor.is_none()
+                && MoveGen::new_legal(&board).any(|mv| {
+                    Some(mv.get_dest()) == target && board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+                })
+        });
+
+        // The king of the side to move turns red while it is in check
+        let checked_king = (self.editor.is_none() && board.checkers().popcnt() > 0)
+            .then(|| board.king_square(board.side_to_move()));
+
+        // Proper board orientation (the human's pieces at the bottom unless flipped)
+        let black_at_bottom = self.black_at_bottom();
+        let (ranks, files): (Vec<usize>, Vec<usize>) = if black_at_bottom {
+            ((0..8).collect(), (0..8).rev().collect())
+        } else {
+            ((0..8).rev().collect(), (0..8).collect())
+        };
+        let label_color = Color::from_rgb(0.5, 0.5, 0.5);
+
+        let squares = ALL_SQUARES.iter().map(|&square| {
+            // The editor's position is shown in place of the game's while setting up
+            let piece = match &self.editor {
+                Some(editor) => editor.piece_on(square),
+                None => board.piece_on(square).zip(board.color_on(square)),
+            };
+            let is_light_square = (square.get_file().to_index() + square.get_rank().to_index()) % 2 == 0;
+
+            // Square colors
+            let theme = &self.board_theme;
+            let hover_preview = hover_preview.filter(|&(hovered, _)| hovered == square);
+            let fill = if self.selected_square == Some(square) {
+                theme.selected
+            } else if self.premove_source == Some(square)
+                || self.premove.is_some_and(|(from, to)| square == from || square == to)
+            {
+                PREMOVE_COLOR
+            } else if self.hint.is_some_and(|mv| square == mv.get_source() || square == mv.get_dest()) {
+                HINT_COLOR
+            } else if self.marked_squares.contains(&square) {
+                MARKED_SQUARE_COLOR
+            } else if legal_targets.contains(&square) {
+                theme.highlight
+            } else if en_passant == Some(square) {
+                EN_PASSANT_COLOR
+            } else if checked_king == Some(square) {
+                Color::from_rgb(0.8, 0.2, 0.2) // Red for a king in check
+            } else if is_light_square {
+                theme.light
+            } else {
+                theme.dark
+            };
+            let fill = match hover_preview {
+                Some((_, true)) => blend(fill, LEGAL_PREVIEW_COLOR),
+                Some((_, false)) => blend(fill, ILLEGAL_PREVIEW_COLOR),
+                None => fill,
+            };
+            SquareView {
+                fill,
+                piece: piece.map(|(piece, color)| self.piece_art(color, piece)),
+                outline: (self.cursor_square == square).then_some(CURSOR_OUTLINE),
+            }
+        });
+        let board_view = BoardView {
+            squares: squares.collect(),
+            arrows: &self.arrows,
+            black_at_bottom,
+            square_size: self.square_size,
+            on_press: if self.editor.is_some() { Message::EditSquare } else { Message::SquareSelected },
+            on_right_press: Message::AnnotationStart,
+            on_right_release: Message::AnnotationEnd,
+            on_hover: Message::SquareHovered,
+            on_unhover: Message::SquareUnhovered,
+            // Kept off while a prompt above the board is waiting on its own answer
+            banner: (self.game.result().is_some()
+                && !self.game_over_dismissed
+                && self.editor.is_none()
+                && !self.confirming_new_game
+                && self.resume_offer.is_none())
+            .then(|| Banner {
+                text: self.game_status(),
+                button: "New Game",
+                on_press: Message::NewGame,
+                on_dismiss: Message::DismissGameOver,
+            }),
+        };
+
+        let rank_labels = ranks.iter().fold(Column::new().spacing(5), |column, &rank| {
+            column.push(
+                Text::new((rank + 1).to_string())
+                    .size(14)
+                    .width(15)
+                    .height(self.square_size)
+                    .horizontal_alignment(alignment::Horizontal::Center)
+                    .vertical_alignment(alignment::Vertical::Center)
+                    .style(label_color)
+            )
+        });
+        let mut rows = Column::new()
+            .spacing(5)
+            .push(Row::new().spacing(5).push(rank_labels).push(board_view));
 
-                let button = Button::new(
-                    Text::new(symbol)
-                        .size(40)
-                        .font(font::Font::with_name("Arial Unicode MS"))
+        // File labels along the bottom edge, offset by the rank label column
+        let file_labels = files.iter().fold(
+            Row::new().spacing(5).push(Text::new("").width(15)),
+            |row, &file| {
+                row.push(
+                    Text::new(((b'a' + file as u8) as char).to_string())
+                        .size(14)
+                        .width(self.square_size)
                         .horizontal_alignment(alignment::Horizontal::Center)
-                        .vertical_alignment(alignment::Vertical::Center)
-                        .style(text_color)
+                        .style(label_color)
                 )
-                .width(70)
-                .height(70)
-                .style(iced::theme::Button::Custom(Box::new(ButtonStyle(button_color))))
-                .on_press(Message::SquareSelected(square));
-                
-                row = row.push(button);
+            },
+        );
+        rows = rows.push(file_labels);
+
+        // Captured pieces sit beside the player who took them, with the material lead
+        let (top_color, bottom_color) = if black_at_bottom {
+            (ChessColor::White, ChessColor::Black)
+        } else {
+            (ChessColor::Black, ChessColor::White)
+        };
+        let material = material_diff(&board);
+        // The side to move is marked beside its clock; once the game is decided nobody is
+        let to_move = self.game.result().is_none().then_some(self.current_turn);
+        let captures_row = |color: ChessColor| {
+            let lead = if color == ChessColor::White { material } else { -material };
+            captured_by(&self.history, &self.move_history, color).into_iter().fold(
+                Row::new().spacing(2).height(24).align_items(Alignment::Center)
+                    .push(Text::new("").width(15)),
+                |row, piece| row.push(Container::new(self.piece_icon(!color, piece, 18.0)).width(20).height(20)),
+            )
+            .push(Text::new(if lead > 0 { format!(" +{}", lead) } else { String::new() }).size(14))
+            .push(Space::with_width(Length::Fill))
+            .push(
+                Text::new(if to_move == Some(color) { "● to move  " } else { "" })
+                    .size(16)
+                    .style(TURN_INDICATOR_COLOR)
+            )
+            .push(
+                Text::new(if self.time_control.is_timed() {
+                    format_clock(match color {
+                        ChessColor::White => self.white_time,
+                        ChessColor::Black => self.black_time,
+                    })
+                } else {
+                    String::new()
+                })
+                .size(20)
+            )
+        };
+        rows = Column::new()
+            .spacing(5)
+            .push(captures_row(top_color))
+            .push(rows)
+            .push(captures_row(bottom_color));
+
+        // Promotion chooser shown on top of the board while a pawn awaits its piece
+        if self.promotion_pending.is_some() {
+            let mut chooser = Row::new().spacing(5);
+            for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                chooser = chooser.push(
+                    Button::new(self.piece_icon(self.player_color, piece, self.square_size * 0.6))
+                    .padding(self.square_size * PIECE_PADDING)
+                    .width(self.square_size)
+                    .height(self.square_size)
+                    .on_press(Message::PromotionChosen(piece))
+                );
             }
-            rows = rows.push(row);
+            rows = Column::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("Promote to:").size(18))
+                .push(chooser)
+                .push(rows);
+        }
+
+        if self.resume_offer.is_some() {
+            rows = Column::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("Resume last game?").size(18))
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Button::new("Yes").on_press(Message::ResumeGame))
+                        .push(Button::new("No").on_press(Message::DeclineResume))
+                )
+                .push(rows);
         }
 
+        if self.confirming_new_game {
+            rows = Column::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("Start a new game? Current game will be lost.").size(18))
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Button::new("Yes").on_press(Message::ConfirmNewGame))
+                        .push(Button::new("No").on_press(Message::CancelNewGame))
+                )
+                .push(rows);
+        }
+
+        let evaluation = match self.engine_lines.first() {
+            // Forced mates are the most important signal, so make them stand out
+            Some(line) if matches!(line.score, Score::Mate(_)) => Text::new(self.score_text(line))
+                .size(22)
+                .style(self.alert_color()),
+            Some(line) => Text::new(format!(
+                "Evaluation: {} for {}",
+                self.score_text(line),
+                color_name(self.eval_viewer())
+            ))
+            .size(16),
+            None => Text::new("").size(16),
+        };
+
+        // Candidate lines stacked in MultiPV order
+        let candidates = self.engine_lines.iter().enumerate().fold(
+            Column::new().spacing(5),
+            |column, (index, line)| {
+                column.push(
+                    Text::new(format!(
+                        "{}. ({}) {}",
+                        index + 1,
+                        self.score_text(line),
+                        uci_pv_to_san(&self.analysis_position, &line.pv)
+                            .into_iter()
+                            .take(if self.analysis_layout { 12 } else { 5 })
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ))
+                    .size(14)
+                )
+            },
+        );
+
         let analysis = Column::new()
             .spacing(10)
-            .push(Text::new(status).size(18))
-            .push(Text::new(&self.engine_evaluation).size(16))
-            .push(Text::new("Principal Variation:").size(16))
+            .push(Text::new(status_line).size(18))
+            .push_maybe(self.think_progress().map(|progress| {
+                ProgressBar::new(0.0..=1.0, progress).height(4).width(250)
+            }))
+            .push(
+                Text::new(match checked_king {
+                    Some(_) if self.game.result().is_none() => {
+                        format!("{} is in check", color_name(board.side_to_move()))
+                    }
+                    _ => String::new(),
+                })
+                .size(16)
+                .style(self.alert_color())
+            )
+            .push(Text::new(format!("Halfmove clock: {} / 100", self.halfmove_clock())).size(14))
+            .push(Text::new(self.opening_text()).size(14))
+            .push(
+                Text::new(if self.square_input.is_empty() {
+                    self.hovered_square.map(|square| format!("Square: {}", square)).unwrap_or_default()
+                } else {
+                    format!("Square: {}_", self.square_input)
+                })
+                .size(14)
+            )
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .push(evaluation)
+                    .push(Text::new(legal_moves_text(&board)).size(14))
+            )
+            .push(
+                Text::new(self.search_stats.map(|stats| stats.to_string()).unwrap_or_default())
+                    .size(14)
+            )
+            .push(Text::new("Engine lines:").size(16))
+            .push(candidates)
+            .push_maybe(self.best_change.map(|(best, was)| {
+                let san = |mv| move_to_san(&self.analysis_position, mv);
+                Text::new(format!("New best: {} (was {})", san(best), san(was))).size(14)
+            }))
+            .push(Text::new("Evaluation history:").size(16))
+            .push(
+                Canvas::new(EvalGraph { points: &self.eval_history, plies: self.history.len() })
+                    .width(if self.analysis_layout { 400 } else { 250 })
+                    .height(if self.analysis_layout { 120 } else { 80 })
+            );
+
+        let engine = Column::new()
+            .spacing(5)
+            .push(Text::new(format!("Engine: {}", self.engine_name())).size(14))
+            .push(Text::new(format!("Path: {}", self.stockfish_path.display())).size(14))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        TextInput::new("Engine path", &self.engine_path_input)
+                            .on_input(Message::EnginePathInputChanged)
+                            .on_submit(Message::SetEnginePath(PathBuf::from(&self.engine_path_input)))
+                            .size(14)
+                            .width(250)
+                    )
+                    .push(
+                        Button::new(Text::new("Set").size(14))
+                            .on_press(Message::SetEnginePath(PathBuf::from(&self.engine_path_input)))
+                    )
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Difficulty:").size(14))
+                    .push(
+                        // Slider tweaks that match no preset leave the picker showing "Custom"
+                        PickList::new(
+                            &Difficulty::ALL[..],
+                            Difficulty::matching(self.skill_level, self.move_time_ms),
+                            Message::SetDifficulty
+                        )
+                        .placeholder("Custom")
+                        .text_size(14)
+                    )
+            )
+            .push(
+                Text::new(format!("Think time: {:.1} s", self.move_time_ms as f64 / 1000.0)).size(14)
+            )
+            .push(
+                Slider::new(100..=30_000u32, self.move_time_ms as u32, |ms| Message::SetMoveTime(ms as u64))
+                    .step(100u32)
+                    .width(250)
+            )
+            .push(Text::new(format!("Skill level: {}", self.skill_level)).size(14))
+            .push(Slider::new(0..=20u8, self.skill_level, Message::SetSkill).width(250));
+        // Not every engine has contempt, so the control only appears for those that do
+        let engine = if self.advertised_options.iter().any(|option| option.name.eq_ignore_ascii_case("Contempt")) {
+            engine
+                .push(Text::new(format!("Contempt: {}", self.contempt)).size(14))
+                .push(Slider::new(-100..=100, self.contempt, Message::SetContempt).width(250))
+        } else {
+            engine
+        };
+        let engine = engine
+            .push(Text::new(format!("Candidate lines: {}", self.multipv)).size(14))
+            .push(Slider::new(1..=5u8, self.multipv, Message::SetMultiPv).width(250))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Scores for:").size(14))
+                    .push(
+                        PickList::new(&EvalPerspective::ALL[..], Some(self.eval_perspective), Message::SetEvalPerspective)
+                            .text_size(14)
+                    )
+            )
+            .push(Text::new(format!("Threads: {}", self.threads)).size(14))
+            .push(
+                Slider::new(1..=max_threads() as u32, self.threads as u32, |n| Message::SetThreads(n as usize))
+                    .width(250)
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Hash (MB):").size(14))
+                    .push(
+                        TextInput::new("128", &self.hash_input)
+                            .on_input(Message::HashInputChanged)
+                            .on_submit(Message::SetHash)
+                            .size(14)
+                            .width(80)
+                    )
+                    .push(Button::new(Text::new("Set").size(14)).on_press(Message::SetHash))
+            )
+            .push(
+                Checkbox::new("Show engine options", self.show_engine_options)
+                    .on_toggle(Message::SetShowEngineOptions)
+                    .text_size(14)
+            );
+        let engine = if self.show_engine_options {
+            engine.push(self.engine_options_panel())
+        } else {
+            engine
+        };
+
+        let side_selector = [ChessColor::White, ChessColor::Black].into_iter().fold(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("Play as:").size(14)),
+            |row, color| {
+                row.push(
+                    Radio::new(color_name(color), color, Some(self.next_player_color), Message::SetPlayerColor)
+                        .size(14)
+                        .text_size(14)
+                )
+            },
+        );
+        let game_mode = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("Mode:").size(14))
+            .push(PickList::new(&GameMode::ALL[..], Some(self.game_mode), Message::SetGameMode).text_size(14))
             .push(
-                Text::new(
-                    self.principal_variation.iter()
-                        .take(5)
-                        .map(|mv| UciMove(*mv).to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                ).size(14)
+                Checkbox::new("Flip after each move", self.auto_flip)
+                    .on_toggle(Message::SetAutoFlip)
+                    .text_size(14)
             );
+        let game_mode = if self.game_mode == GameMode::EngineVsEngine {
+            Column::new()
+                .spacing(5)
+                .push(game_mode)
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Button::new(Text::new(if self.autoplaying { "Stop" } else { "Start" }).size(14))
+                                .on_press_maybe(
+                                    self.game.result().is_none().then_some(Message::SetAutoplay(!self.autoplaying))
+                                )
+                        )
+                        .push(
+                            Text::new(format!("Move time: {:.1} s", self.autoplay_move_time_ms as f64 / 1000.0))
+                                .size(14)
+                        )
+                        .push(
+                            Slider::new(100..=30_000u32, self.autoplay_move_time_ms as u32, |ms| {
+                                Message::SetAutoplayMoveTime(ms as u64)
+                            })
+                            .step(100u32)
+                            .width(150)
+                        )
+                )
+        } else {
+            Column::new().push(game_mode)
+        };
+
+        // Numbered two-column move list; a game starting with Black to move opens with "1. ..."
+        // Each entry is (text, mistake mark, ply reached by clicking it)
+        let black_first = self.starts_with_black();
+        let mut padded: Vec<(String, Option<&str>, Option<usize>)> = Vec::new();
+        if black_first {
+            padded.push(("...".to_string(), None, None));
+        }
+        padded.extend(self.san_history.iter().enumerate().map(|(index, san)| {
+            let mark = self.blunder_mark(index + 1);
+            (format!("{}{}", san, mark.unwrap_or("")), mark, Some(index + 1))
+        }));
+        // Undone moves stay listed after the current one so they can be jumped to as well
+        let played = self.san_history.len();
+        padded.extend(
+            self.redo_san().into_iter().enumerate().map(|(index, san)| (san, None, Some(played + index + 1)))
+        );
+        // Mistakes stand out in red, undone moves are dimmed, and the move on the board is marked
+        let cell = |entry: Option<&(String, Option<&str>, Option<usize>)>| -> Element<Message> {
+            let Some((san, mark, Some(ply))) = entry else {
+                let text = entry.map_or(String::new(), |(san, ..)| san.clone());
+                return Text::new(text).size(14).width(60).into();
+            };
+            let text = Text::new(san.clone()).size(14);
+            let text = match mark {
+                _ if *ply > played => text.style(label_color),
+                Some(_) => text.style(self.alert_color()),
+                None => text,
+            };
+            Button::new(text)
+                .padding([0, 2])
+                .width(60)
+                .style(if *ply == played { iced::theme::Button::Primary } else { iced::theme::Button::Text })
+                .on_press(Message::GotoMove(*ply))
+                .into()
+        };
+        let move_list = padded.chunks(2).enumerate().fold(
+            Column::new().spacing(2),
+            |list, (index, pair)| {
+                list.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new(format!("{}.", index + 1)).size(14).width(35))
+                        .push(cell(pair.first()))
+                        .push(cell(pair.get(1)))
+                )
+            },
+        );
+        let moves = Column::new()
+            .spacing(5)
+            .push(Text::new("Moves:").size(16))
+            .push(Scrollable::new(move_list).height(200));
+
+        let board_theme = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("Board:").size(14))
+            .push(
+                PickList::new(&BoardTheme::ALL[..], Some(self.board_theme), Message::SetBoardTheme)
+                    .text_size(14)
+            )
+            .push(Text::new("Pieces:").size(14))
+            .push(PickList::new(&PieceStyle::ALL[..], Some(self.piece_style), Message::SetPieceStyle).text_size(14))
+            .push(Text::new("Theme:").size(14))
+            .push(PickList::new(Theme::ALL, Some(self.theme.clone()), Message::SetTheme).text_size(14))
+            .push(Text::new("Scale:").size(14))
+            .push(PickList::new(&UiScale::ALL[..], Some(self.ui_scale), Message::SetUiScale).text_size(14));
+        let layout_picker = || {
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("Layout:").size(14))
+                .push(PickList::new(&LayoutMode::ALL[..], Some(self.layout), Message::SetLayout).text_size(14))
+        };
 
-        let controls = Column::new()
-            .spacing(20)
-            .push(Button::new("New Game").on_press(Message::NewGame))
-            .push(analysis);
+        let dark_mode = Checkbox::new("Dark mode", self.is_dark())
+            .on_toggle(|_| Message::ToggleTheme)
+            .text_size(14);
+        let mute = Checkbox::new("Mute sounds", self.muted)
+            .on_toggle(Message::SetMuted)
+            .text_size(14);
+        let chime = Checkbox::new("Chime when it's my turn", self.turn_chime)
+            .on_toggle(Message::SetTurnChime)
+            .text_size(14);
+        let debug_toggle = Checkbox::new("Debug view", self.debug_view)
+            .on_toggle(Message::SetDebugView)
+            .text_size(14);
+
+        let debug = if self.debug_view {
+            Column::new()
+                .spacing(2)
+                .push(Text::new(format!("Position occurrences: {}", self.repetition_count())).size(12))
+                .push(Text::new(format!("Halfmove clock: {}", self.halfmove_clock())).size(12))
+                .push(Text::new(format!("can_declare_draw: {}", self.game.can_declare_draw())).size(12))
+        } else {
+            Column::new()
+        };
+
+        let time_control = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("Time control:").size(14))
+            .push(
+                PickList::new(&TimeControl::ALL[..], Some(self.time_control), Message::SetTimeControl)
+                    .text_size(14)
+            )
+            .push(
+                Checkbox::new("Engine plays on the clock", self.engine_uses_clock)
+                    .on_toggle(Message::SetEngineUsesClock)
+                    .text_size(14)
+            );
 
-        Container::new(
+        let position_input = || {
             Row::new()
-                .push(rows)
+                .spacing(5)
+                .push(
+                    TextInput::new("Paste a FEN to load", &self.fen_input)
+                        .on_input(Message::FenInputChanged)
+                        .on_submit(Message::LoadFen)
+                        .size(14)
+                        .width(300)
+                )
+                .push(Button::new(Text::new("Load FEN").size(14)).on_press(Message::LoadFen))
+                .push(Button::new(Text::new("Set Up Position").size(14)).on_press(Message::EditPosition))
+        };
+        let navigation = Row::new()
+            .spacing(10)
+            .push(Button::new("Undo").on_press_maybe((!self.history.is_empty()).then_some(Message::Undo)))
+            .push(Button::new("Redo").on_press_maybe((!self.redo_stack.is_empty()).then_some(Message::Redo)))
+            .push(Button::new("Flip").on_press(Message::FlipBoard));
+
+        let controls = if self.analysis_layout {
+            Column::new()
+                .spacing(20)
+                .push(Row::new().push(Button::new("Back to Game").on_press(Message::ToggleLayout)))
+                .push(navigation)
+                .push(position_input())
+                .push(Row::new().spacing(20).push(moves).push(analysis))
+        } else {
+            Column::new()
+                .spacing(20)
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Button::new("New Game").on_press(Message::NewGame))
+                        .push(navigation)
+                        .push(Button::new("Resign").on_press(Message::Resign))
+                        .push(Button::new("Analysis Mode").on_press(Message::ToggleLayout))
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Button::new("Copy FEN").on_press(Message::CopyFen))
+                        .push(Button::new("Export PGN").on_press(Message::ExportPgn))
+                        .push(Button::new("Copy PGN").on_press(Message::CopyPgn))
+                )
+                .push(position_input())
+                .push(
+                    Row::new()
+                        .spacing(5)
+                        .push(
+                            TextInput::new("Type a move, e.g. Nf3 or g1f3", &self.move_input)
+                                .on_input(Message::MoveInputChanged)
+                                .on_submit(Message::TextMove(self.move_input.clone()))
+                                .size(14)
+                                .width(300)
+                        )
+                        .push(
+                            Button::new(Text::new("Play").size(14))
+                                .on_press(Message::TextMove(self.move_input.clone()))
+                        )
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            Button::new("Offer Draw")
+                                .on_press_maybe(self.draw_offer.is_none().then_some(Message::OfferDraw))
+                        )
+                        .push(
+                            Button::new("Accept Draw")
+                                .on_press_maybe(
+                                    (self.draw_offer == Some(!self.player_color)).then_some(Message::AcceptDraw)
+                                )
+                        )
+                        .push(
+                            Button::new("Claim Draw")
+                                .on_press_maybe(self.draw_claimable.then_some(Message::ClaimDraw))
+                        )
+                        .push(
+                            Button::new("Hint").on_press_maybe(
+                                (self.is_human_turn() && self.game.result().is_none() && !self.hint_pending)
+                                    .then_some(Message::RequestHint)
+                            )
+                        )
+                        .push(
+                            Button::new("Force Move")
+                                .on_press_maybe(self.force_move.is_some().then_some(Message::ForceMove))
+                        )
+                        .push(if self.analyzing {
+                            Button::new("Stop Analysis").on_press(Message::StopAnalysis)
+                        } else {
+                            Button::new("Analyze").on_press_maybe(
+                                (self.is_human_turn() && self.engine_task.is_none())
+                                    .then_some(Message::StartAnalysis)
+                            )
+                        })
+                )
+                .push(game_mode)
+                .push(side_selector)
+                .push(time_control)
+                .push(board_theme)
+                .push(layout_picker())
+                .push(Row::new().spacing(20).push(dark_mode).push(mute).push(chime).push(debug_toggle))
+                .push(debug)
+                .push(moves)
+                .push(engine)
+                .push(analysis)
+        };
+
+        // Evaluation bar: White's share grows from White's side of the board
+        let white_share = self.eval_cp.map_or(50, |cp| {
+            (cp.clamp(-EVAL_BAR_LIMIT_CP, EVAL_BAR_LIMIT_CP) + EVAL_BAR_LIMIT_CP) * 50 / EVAL_BAR_LIMIT_CP
+        }) as u16;
+        let white_part = Container::new(Text::new(""))
+            .width(Length::Fill)
+            .height(Length::FillPortion(white_share))
+            .style(iced::theme::Container::Custom(Box::new(FillStyle(Color::from_rgb(0.95, 0.95, 0.95)))));
+        let black_part = Container::new(Text::new(""))
+            .width(Length::Fill)
+            .height(Length::FillPortion(100 - white_share))
+            .style(iced::theme::Container::Custom(Box::new(FillStyle(Color::from_rgb(0.15, 0.15, 0.15)))));
+        let eval_bar = if black_at_bottom {
+            Column::new().push(white_part).push(black_part)
+        } else {
+            Column::new().push(black_part).push(white_part)
+        }
+        .width(20)
+        .height(self.square_size * 8.0 + 7.0 * 5.0);
+
+        let controls: Element<Message> = match &self.editor {
+            Some(editor) => self.editor_panel(editor),
+            None => controls.into(),
+        };
+        let board = Row::new().push(eval_bar).push(rows).spacing(30).align_items(Alignment::Center);
+        // The editor needs its palette, so it keeps the panel beside the board in every layout
+        let layout = if self.editor.is_some() { LayoutMode::Side } else { self.layout };
+        let content: Element<Message> = match layout {
+            LayoutMode::Side => Row::new()
+                .push(board)
                 .push(controls)
                 .spacing(30)
                 .align_items(Alignment::Center)
-        )
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .center_x()
-        .center_y()
-        .padding(30)
-        .into()
-    }
-}
+                .into(),
+            LayoutMode::Below => Scrollable::new(
+                Column::new()
+                    .push(board)
+                    .push(controls)
+                    .spacing(30)
+                    .align_items(Alignment::Center)
+                    .width(Length::Fill)
+            )
+            .into(),
+            // Only the way back to the other layouts stays
+            LayoutMode::BoardOnly => Column::new()
+                .push(board)
+                .push(layout_picker())
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into(),
+        };
 
-struct ButtonStyle(Color);
-impl iced::widget::button::StyleSheet for ButtonStyle {
-    type Style = iced::Theme;
+        // Raw position state, always visible along the bottom edge
+        let status_bar = Text::new(format!(
+            "{}   Move {}   {} to move",
+            self.current_fen(),
+            self.fullmove_number(),
+            color_name(self.game.side_to_move()),
+        ))
+        .font(Font::MONOSPACE)
+        .size(12)
+        .style(label_color);
 
-    fn active(&self, _style: &Self::Style) -> iced::widget::button::Appearance {
-        iced::widget::button::Appearance {
-            background: Some(self.0.into()),
-            border: iced::Border {
-                radius: 3.0.into(),
-                width: 0.0,
-                color: Color::TRANSPARENT,
-            },
-            text_color: Color::TRANSPARENT,
-            ..Default::default()
-        }
+        let error_banner = self.error.as_ref().map(|error| {
+            Container::new(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(error.clone()).size(14).style(Color::WHITE).width(Length::Fill))
+                    .push(Button::new(Text::new("Dismiss").size(14)).on_press(Message::ClearError))
+            )
+            .width(Length::Fill)
+            .padding([6, 10])
+            .style(iced::theme::Container::Custom(Box::new(FillStyle(ERROR_BANNER_COLOR))))
+        });
+
+        Column::new()
+            .push_maybe(error_banner)
+            .push(
+                Container::new(content)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .center_x()
+                .center_y()
+                .padding(30)
+            )
+            .push(Container::new(status_bar).width(Length::Fill).padding([4, 10]))
+            .into()
     }
 }
 
-fn white_piece_symbol(piece: Option<Piece>) -> String {
-    match piece {
-        Some(Piece::King) => '♔',
-        Some(Piece::Queen) => '♕',
-        Some(Piece::Rook) => '♖',
-        Some(Piece::Bishop) => '♗',
-        Some(Piece::Knight) => '♘',
-        Some(Piece::Pawn) => '♙',
-        None => ' ',
-    }.to_string()
-}
+impl ChessApp {
+    /// Applies one message to the state; `update` wraps this so every change gets saved.
+    fn handle_message(&mut self, message: Message) -> Command<Message> {
+        // Once the game is decided only moves are blocked; settings and New Game stay available
+        if self.game.result().is_some()
+            && matches!(
+                message,
+                Message::SquareSelected(_)
+                    | Message::PromotionChosen(_)
+                    | Message::TextMove(_)
+                    | Message::EngineMove(..)
+                    | Message::Resign
+                    | Message::OfferDraw
+                    | Message::AcceptDraw
+                    | Message::ClaimDraw
+                    | Message::Tick(_)
+                    | Message::Redo
+            )
+        {
+            return Command::none();
+        }
 
-fn black_piece_symbol(piece: Option<Piece>) -> String {
-    match piece {
-        Some(Piece::King) => '♚',
-        Some(Piece::Queen) => '♛',
-        Some(Piece::Rook) => '♜',
-        Some(Piece::Bishop) => '♝',
-        Some(Piece::Knight) => '♞',
-        Some(Piece::Pawn) => '♟',
-        None => ' ',
-    }.to_string()
-}
-
-fn get_stockfish_move(path: PathBuf, game: Game) -> Command<Message> {
-    Command::perform(
-        async move {
-            let mut stockfish = AsyncCommand::new(&path)
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .spawn()
-                .expect("Failed to start Stockfish");
-
-            let fen = game.current_position().to_string();
-            let commands = format!(
-                "uci\nisready\nucinewgame\nposition fen {}\n\
-                 setoption name Skill Level value 20\n\
-                 setoption name Contempt value 100\n\
-                 setoption name UCI_LimitStrength value false\n\
-                 go movetime 5000\n",
-                fen
-            );
-            if let Some(mut stdin) = stockfish.stdin.take() {
-                stdin.write_all(commands.as_bytes()).await.expect("Write failed");
-                stdin.flush().await.expect("Flush failed");
+        match message {
+            // In the analysis layout the board is moved only by navigation and setup
+            Message::SquareSelected(_) | Message::PromotionChosen(_) | Message::TextMove(_) if self.analysis_layout => {
+                Command::none()
             }
+            Message::SquareSelected(square) => {
+                if self.is_human_turn() {
+                    // Clicking anywhere while the chooser is open cancels the promotion
+                    self.promotion_pending = None;
 
-            let mut output = String::new();
-            let mut evaluation = String::new();
-            let mut pv = Vec::new();
-            let mut best_move = None;
+                    if self.selected_square == Some(square) {
+                        self.selected_square = None;
+                        return Command::none();
+                    }
 
-            if let Some(mut stdout) = stockfish.stdout.take() {
-                let mut buf = [0u8; 1024];
-                loop {
-                    let n = stdout.read(&mut buf).await.expect("Read failed");
-                    if n == 0 { break; }
-                    output.push_str(&String::from_utf8_lossy(&buf[..n]));
-                    
-                    for line in output.lines() {
-                        if line.starts_with("info") {
-                            if let Some(score_idx) = line.find("score cp") {
-                                let parts: Vec<&str> = line.split_whitespace().collect();
-                                if let Some(cp_idx) = parts.iter().position(|&s| s == "cp") {
-                                    if let Some(cp) = parts.get(cp_idx + 1) {
-                                        evaluation = format!("Evaluation: {}{}", 
-                                            if parts.contains(&"lowerbound") { "≥" } 
-                                            else if parts.contains(&"upperbound") { "≤" } 
-                                            else { "" },
-                                            cp
-                                        );
-                                    }
-                                }
-                            }
-                            if let Some(pv_idx) = line.find("pv") {
-                                pv = line[pv_idx+3..]
-                                    .split_whitespace()
-                                    .filter_map(|m| ChessMove::from_str(m).ok())
-                                    .collect();
-                            }
+                    let board = self.game.current_position();
+                    if let Some(selected) = self.selected_square {
+                        if is_promotion(&board, selected, square) {
+                            self.promotion_pending = Some(PromotionPending {
+                                from: selected,
+                                to: square,
+                            });
+                            return Command::none();
                         }
-                        if line.starts_with("bestmove") {
-                            best_move = line.split_whitespace()
-                                .nth(1)
-                                .and_then(|m| ChessMove::from_str(m).ok());
-                            break;
+
+                        let mv = ChessMove::new(selected, square, None);
+                        if board.legal(mv) {
+                            return self.play_human_move(mv);
                         }
                     }
-                    
-                    if best_move.is_some() {
-                        break;
+                    // Only the side to move's own pieces can be picked up. Any other click that
+                    // isn't a legal move keeps the current selection so another destination can
+                    // be tried
+                    if board.piece_on(square).is_some() && board.color_on(square) == Some(self.current_turn) {
+                        self.selected_square = Some(square);
+                    }
+                } else if self.game_mode == GameMode::HumanVsEngine {
+                    // During the engine's turn clicks only queue a premove; the live selection
+                    // and the game are left untouched. Engine-vs-engine play has no human turn
+                    // to play one on
+                    self.premove = None;
+                    if self.premove_source == Some(square) {
+                        self.premove_source = None;
+                    } else if let Some(source) = self.premove_source.take() {
+                        self.premove = Some((source, square));
+                    } else if self.game.current_position().color_on(square) == Some(self.player_color) {
+                        self.premove_source = Some(square);
+                    }
+                }
+                Command::none()
+            }
+            Message::PromotionChosen(piece) => {
+                if !self.is_human_turn() {
+                    self.promotion_pending = None;
+                    return Command::none();
+                }
+                if let Some(PromotionPending { from, to }) = self.promotion_pending.take() {
+                    let mv = ChessMove::new(from, to, Some(piece));
+                    if self.game.current_position().legal(mv) {
+                        return self.play_human_move(mv);
                     }
                 }
+                Command::none()
+            }
+            Message::ClearError => {
+                self.error = None;
+                Command::none()
+            }
+            Message::ForceMove => {
+                if let Some(stop) = self.force_move.take() {
+                    let _ = stop.send(());
+                }
+                Command::none()
             }
+            // A reply can already be queued when its search is aborted by undo, a new game, or
+            // anything else that changes the position under it
+            Message::EngineMove(id, _) | Message::EngineProgress(id, _) if id != self.search_id => Command::none(),
+            Message::EngineMove(_, Err(error)) => self.handle_message(Message::EngineError(error)),
+            Message::EngineMove(_, Ok(EngineReport { best_move, lines, stats, engine_name: name })) => {
+                self.engine_task = None;
+                self.force_move = None;
+                self.think_budget = None;
+                self.search_stats = Some(stats);
+                if name.is_some() {
+                    self.engine_name = name;
+                }
+                // No move means the position is already terminal
+                let Some(mv) = best_move else {
+                    // Checkmate and stalemate already show as the game's result
+                    if self.game.current_position().status() == BoardStatus::Ongoing {
+                        self.status = Some("Engine has no move to play".to_string());
+                    }
+                    return Command::none();
+                };
+                // The engine's lines all start from the position it searched, before its move
+                let searched = self.game.current_position();
+                let searched_ply = self.history.len();
+                let played_main_line = lines.first().and_then(|line| line.pv.first()) == Some(&mv);
 
-            (
-                best_move.expect("No best move found"),
-                evaluation,
-                pv
-            )
-        },
-        Message::EngineMove
-    )
-}
\ No newline at end of file
+                // A pending offer from the human is answered from the engine's fresh evaluation
+                let answered_offer = self.draw_offer.take() == Some(self.player_color);
+                if answered_offer && engine_accepts_draw(&lines) && self.game.accept_draw() {
+                    self.set_engine_lines(lines, searched);
+                    return Command::none();
+                }
+
+                if self.apply_move(mv) {
+                    self.redo_stack.clear();
+                    self.set_engine_lines(lines, searched);
+                    if let Some(cp) = self.eval_cp {
+                        self.eval_history.push((searched_ply, cp));
+                        // Playing its main line leaves the engine's evaluation standing for the
+                        // position it moved into, which is the one the human now moves from
+                        if played_main_line {
+                            self.eval_history.push((searched_ply + 1, cp));
+                        }
+                    }
+                    // Playing itself, the engine answers its own move straight away
+                    if self.game_mode == GameMode::EngineVsEngine {
+                        if self.autoplaying && self.game.result().is_none() {
+                            return self.request_engine_move();
+                        }
+                        self.autoplaying = false;
+                        return Command::none();
+                    }
+
+                    self.current_turn = self.player_color;
+                    self.status = answered_offer.then(|| format!("{} declines the draw", self.engine_name()));
+                    // Late in a level game the engine proposes a draw itself
+                    if self.move_history.len() >= ENGINE_DRAW_OFFER_MIN_PLIES
+                        && engine_accepts_draw(&self.engine_lines)
+                        && self.game.offer_draw(!self.player_color)
+                    {
+                        self.draw_offer = Some(!self.player_color);
+                    }
+
+                    // A half-entered premove carries over as the selection
+                    if let Some(source) = self.premove_source.take() {
+                        self.selected_square = Some(source);
+                    }
+                    // A queued premove is played only if it is legal in the new position
+                    if let Some((from, to)) = self.premove.take() {
+                        let board = self.game.current_position();
+                        let promotion = is_promotion(&board, from, to).then_some(Piece::Queen);
+                        let mv = ChessMove::new(from, to, promotion);
+                        if self.game.result().is_none() && board.legal(mv) {
+                            return self.play_human_move(mv);
+                        }
+                    }
+                    if self.turn_chime && self.game.result().is_none() {
+                        self.play_sound(SoundEvent::YourTurn);
+                        if !self.window_focused {
+                            return window::request_user_attention(
+                                window::Id::MAIN,
+                                Some(window::UserAttention::Informational),
+                            );
+                        }
+                    }
+                }
+                Command::none()
+            }
+            Message::EngineError(error) => {
+                self.engine_task = None;
+                self.force_move = None;
+                self.think_budget = None;
+                self.premove = None;
+                self.premove_source = None;
+                self.analyzing = false;
+                self.error = Some(format!("Engine error: {}", error));
+                Command::none()
+            }
+            Message::EnginePathInputChanged(input) => {
+                self.engine_path_input = input;
+                Command::none()
+            }
+            Message::SetEnginePath(path) => {
+                self.engine_path_input = path.display().to_string();
+                self.stockfish_path = path.clone();
+                self.engine_name = None;
+                self.advertised_options.clear();
+                self.save_config();
+                Command::perform(engine::probe(path), Message::EngineProbed)
+            }
+            Message::EngineProbed(result) => {
+                // Checking the handshake up front catches a path to something that isn't an
+                // engine before any search waits on it
+                match result {
+                    Ok(profile) => {
+                        self.engine_name = profile.name.or(self.engine_name.take());
+                        self.advertised_options = profile.options;
+                    }
+                    Err(error) => self.error = Some(format!("Engine error: {}", error)),
+                }
+                Command::none()
+            }
+            Message::SetEngineOption(name, value) => {
+                self.engine_options.insert(name, value);
+                self.save_config();
+                Command::none()
+            }
+            Message::ResetEngineOptions => {
+                self.engine_options.clear();
+                self.save_config();
+                Command::none()
+            }
+            Message::SetShowEngineOptions(show) => {
+                self.show_engine_options = show;
+                Command::none()
+            }
+            Message::SetMoveTime(ms) => {
+                self.move_time_ms = ms;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetSkill(level) => {
+                self.skill_level = level;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetContempt(contempt) => {
+                self.contempt = contempt;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetDifficulty(difficulty) => {
+                let (skill_level, move_time_ms) = difficulty.settings();
+                self.skill_level = skill_level;
+                self.move_time_ms = move_time_ms;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetMultiPv(lines) => {
+                self.multipv = lines;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetThreads(threads) => {
+                self.threads = threads;
+                self.save_config();
+                Command::none()
+            }
+            Message::HashInputChanged(input) => {
+                self.hash_input = input;
+                Command::none()
+            }
+            Message::SetHash => {
+                match self.hash_input.trim().parse::<usize>() {
+                    Ok(mb) if mb >= 1 && max_hash_mb().is_none_or(|max| mb <= max) => {
+                        self.hash_mb = mb;
+                        self.save_config();
+                    }
+                    Ok(mb) if mb >= 1 => {
+                        self.status = Some(format!("{} MB of hash is more than this machine can spare", mb));
+                    }
+                    _ => self.status = Some(format!("Invalid hash size: {}", self.hash_input)),
+                }
+                self.hash_input = self.hash_mb.to_string();
+                Command::none()
+            }
+            Message::SetPlayerColor(color) => {
+                // Takes effect from the next New Game; switching sides mid-game would hand the
+                // engine a turn it never started searching
+                self.next_player_color = color;
+                Command::none()
+            }
+            // Only an unfinished game with moves in it is worth asking about
+            Message::NewGame if self.game.result().is_none() && !self.history.is_empty() => {
+                self.confirming_new_game = true;
+                Command::none()
+            }
+            Message::NewGame | Message::ConfirmNewGame => self.start_game(Game::new(), "New game"),
+            Message::SetGameMode(mode) => {
+                // Whoever is to move keeps the move; in vs-engine play the engine takes the
+                // other side
+                self.cancel_search();
+                self.game_mode = mode;
+                self.autoplaying = false;
+                self.reviewing = false;
+                self.player_color = self.game.side_to_move();
+                self.current_turn = self.player_color;
+                self.premove = None;
+                self.premove_source = None;
+                Command::none()
+            }
+            Message::SetAutoplay(running) => {
+                self.autoplaying = running && self.game_mode == GameMode::EngineVsEngine;
+                if self.autoplaying {
+                    if self.engine_task.is_none() && self.game.result().is_none() {
+                        return self.request_engine_move();
+                    }
+                } else {
+                    // Stopped, the board is free for moving pieces by hand
+                    self.cancel_search();
+                    self.premove = None;
+                    self.premove_source = None;
+                    self.hand_over_to_side_to_move();
+                    if self.game.result().is_none() {
+                        self.status = Some("Autoplay stopped".to_string());
+                    }
+                }
+                Command::none()
+            }
+            Message::SetAutoplayMoveTime(ms) => {
+                // Applies from the next move searched
+                self.autoplay_move_time_ms = ms;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetAutoFlip(enabled) => {
+                self.auto_flip = enabled;
+                self.save_config();
+                Command::none()
+            }
+            Message::ResumeGame => {
+                let Some(saved) = self.resume_offer.take() else {
+                    return Command::none();
+                };
+                let Ok(board) = Board::from_str(&saved.start_fen) else {
+                    self.error = Some("The saved game could not be read".to_string());
+                    return Command::none();
+                };
+                self.game_mode = GameMode::ALL.iter()
+                    .copied()
+                    .find(|mode| mode.to_string() == saved.game_mode)
+                    .unwrap_or(GameMode::HumanVsEngine);
+                if let Some(time_control) = TimeControl::ALL.iter()
+                    .copied()
+                    .find(|time_control| time_control.to_string() == saved.time_control)
+                {
+                    self.time_control = time_control;
+                }
+                self.next_player_color = if saved.human_plays_black { ChessColor::Black } else { ChessColor::White };
+                self.reset_game(Game::new_with_board(board));
+                // Replaying isn't news, so the moves go in silently
+                let sounds = self.sounds.take();
+                for mv in saved.moves.iter().map_while(|uci| ChessMove::from_str(uci).ok()) {
+                    if !self.apply_move(mv) {
+                        break;
+                    }
+                }
+                self.sounds = sounds;
+                self.begin_turn("Game resumed")
+            }
+            Message::DeclineResume => {
+                self.resume_offer = None;
+                SavedGame::clear();
+                Command::none()
+            }
+            Message::DismissGameOver => {
+                self.game_over_dismissed = true;
+                Command::none()
+            }
+            Message::CancelNewGame => {
+                self.confirming_new_game = false;
+                Command::none()
+            }
+            Message::EditPosition => {
+                self.cancel_search();
+                self.selected_square = None;
+                self.premove = None;
+                self.premove_source = None;
+                self.editor = Some(PositionEditor::new(&self.game.current_position()));
+                Command::none()
+            }
+            Message::EditSquare(square) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.click(square);
+                }
+                Command::none()
+            }
+            Message::SetPalette(palette) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.palette = palette;
+                }
+                Command::none()
+            }
+            Message::SetEditSideToMove(color) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.set_side_to_move(color);
+                }
+                Command::none()
+            }
+            Message::ClearEditBoard => {
+                if let Some(editor) = &mut self.editor {
+                    editor.clear();
+                }
+                Command::none()
+            }
+            Message::ResetEditBoard => {
+                if let Some(editor) = &mut self.editor {
+                    editor.reset();
+                }
+                Command::none()
+            }
+            Message::FinishEdit => {
+                let Some(editor) = &self.editor else {
+                    return Command::none();
+                };
+                match editor.finish() {
+                    Ok(board) => {
+                        self.editor = None;
+                        self.start_game(Game::new_with_board(board), "Position set up")
+                    }
+                    Err(error) => {
+                        self.error = Some(format!("Can't play this position: {}", error));
+                        Command::none()
+                    }
+                }
+            }
+            Message::CancelEdit => {
+                self.editor = None;
+                // The clocks were paused while editing, and the pause isn't charged to anyone
+                self.last_tick = None;
+                // Leaving the editor resumes the game, including a search it interrupted
+                if self.game.result().is_none() && !self.is_human_turn() {
+                    return self.request_engine_move();
+                }
+                Command::none()
+            }
+            Message::FenInputChanged(input) => {
+                self.fen_input = input;
+                Command::none()
+            }
+            Message::MoveInputChanged(input) => {
+                self.move_input = input;
+                Command::none()
+            }
+            Message::TextMove(text) => {
+                if !self.is_human_turn() || self.editor.is_some() {
+                    return Command::none();
+                }
+                let text = text.trim();
+                let board = self.game.current_position();
+                // SAN is tried first; UCI ("g1f3", "e7e8q") is the fallback
+                let Ok(mv) = ChessMove::from_san(&board, text).or_else(|_| ChessMove::from_str(text)) else {
+                    self.status = Some(format!("Not a move: {}", text));
+                    return Command::none();
+                };
+                if !board.legal(mv) {
+                    self.status = Some(format!("Illegal move: {}", text));
+                    return Command::none();
+                }
+                self.move_input.clear();
+                self.promotion_pending = None;
+                self.play_human_move(mv)
+            }
+            Message::LoadFen => match Board::from_str(self.fen_input.trim()) {
+                Ok(board) => self.start_game(Game::new_with_board(board), "Position loaded"),
+                Err(error) => {
+                    self.error = Some(format!("Invalid FEN: {}", error));
+                    Command::none()
+                }
+            },
+            Message::Undo => {
+                if self.history.is_empty() {
+                    self.status = Some("Nothing to undo".to_string());
+                    return Command::none();
+                }
+                self.cancel_search();
+                // Stepping through the game by hand pauses a game the engine plays against itself
+                self.autoplaying = false;
+                self.reviewing = false;
+                // Step back past the engine reply to the last position where the human was to move
+                let ply = (0..self.history.len())
+                    .rev()
+                    .find(|&ply| !self.is_engine_side(self.history[ply].side_to_move()))
+                    .unwrap_or(0);
+                self.rewind_to(ply);
+                // Undoing the engine's opening move as Black hands the move back to the engine
+                if self.is_engine_side(self.game.side_to_move()) {
+                    return self.request_engine_move();
+                }
+                self.hand_over_to_side_to_move();
+                self.status = Some("Move undone".to_string());
+                Command::none()
+            }
+            Message::Redo => {
+                if self.redo_stack.is_empty() {
+                    return Command::none();
+                }
+                self.cancel_search();
+                self.autoplaying = false;
+                self.reviewing = false;
+                self.selected_square = None;
+                self.premove = None;
+                self.premove_source = None;
+                self.promotion_pending = None;
+                self.draw_offer = None;
+                // Replay through the engine's reply, back to the human's next turn
+                while let Some(mv) = self.redo_stack.pop() {
+                    if !self.apply_move(mv) {
+                        self.redo_stack.clear();
+                        break;
+                    }
+                    if !self.is_engine_side(self.game.side_to_move()) {
+                        break;
+                    }
+                }
+                if self.game.result().is_some() {
+                    return Command::none();
+                }
+                if self.is_engine_side(self.game.side_to_move()) {
+                    return self.request_engine_move();
+                }
+                self.hand_over_to_side_to_move();
+                self.status = Some("Move redone".to_string());
+                Command::none()
+            }
+            Message::GotoMove(ply) => {
+                let current = self.history.len();
+                if ply == current || ply > current + self.redo_stack.len() {
+                    return Command::none();
+                }
+                self.cancel_search();
+                self.autoplaying = false;
+                if ply < current {
+                    self.rewind_to(ply);
+                } else {
+                    self.selected_square = None;
+                    self.premove = None;
+                    self.premove_source = None;
+                    self.promotion_pending = None;
+                    self.draw_offer = None;
+                    while self.history.len() < ply {
+                        let Some(mv) = self.redo_stack.pop() else {
+                            break;
+                        };
+                        if !self.apply_move(mv) {
+                            self.redo_stack.clear();
+                            break;
+                        }
+                    }
+                }
+                // The player keeps their side, so the board doesn't turn with each jump; the
+                // side to move there can be moved by hand, and the engine only answers once a
+                // move is made
+                self.reviewing = true;
+                self.current_turn = self.game.side_to_move();
+                self.status = Some(format!("Viewing move {}", self.history.len()));
+                Command::none()
+            }
+            Message::FlipBoard => {
+                self.flipped = !self.flipped;
+                Command::none()
+            }
+            Message::OfferDraw => {
+                if self.draw_offer.is_none() && self.game.offer_draw(self.player_color) {
+                    self.draw_offer = Some(self.player_color);
+                }
+                Command::none()
+            }
+            Message::AcceptDraw => {
+                if self.draw_offer == Some(!self.player_color) && self.game.accept_draw() {
+                    self.cancel_search();
+                    self.draw_offer = None;
+                    self.selected_square = None;
+                    self.promotion_pending = None;
+                    self.play_sound(SoundEvent::GameEnd);
+                }
+                Command::none()
+            }
+            Message::MoveCursor(right, up) => {
+                let (right, up) = if self.black_at_bottom() { (-right, -up) } else { (right, up) };
+                let file = (self.cursor_square.get_file().to_index() as i8 + right).clamp(0, 7);
+                let rank = (self.cursor_square.get_rank().to_index() as i8 + up).clamp(0, 7);
+                self.cursor_square = Square::make_square(
+                    Rank::from_index(rank as usize),
+                    File::from_index(file as usize)
+                );
+                Command::none()
+            }
+            Message::SelectCursor if self.editor.is_some() => self.handle_message(Message::EditSquare(self.cursor_square)),
+            Message::SelectCursor => self.handle_message(Message::SquareSelected(self.cursor_square)),
+            Message::SquareKey(key) => {
+                if ('a'..='h').contains(&key) {
+                    self.square_input = key.to_string();
+                    return Command::none();
+                }
+                self.square_input.push(key);
+                let typed = Square::from_str(&std::mem::take(&mut self.square_input));
+                match typed {
+                    // A typed square acts like clicking it, moving the keyboard cursor there too
+                    Ok(square) => {
+                        self.cursor_square = square;
+                        self.handle_message(Message::SelectCursor)
+                    }
+                    Err(_) => Command::none(),
+                }
+            }
+            Message::AnnotationStart(square) => {
+                self.annotation_start = Some(square);
+                Command::none()
+            }
+            Message::AnnotationEnd(square) => {
+                // Releasing where the drag began marks the square; anywhere else draws an arrow
+                match self.annotation_start.take() {
+                    Some(start) if start == square => toggle(&mut self.marked_squares, square),
+                    Some(start) => toggle(&mut self.arrows, (start, square)),
+                    None => {}
+                }
+                Command::none()
+            }
+            Message::ClearAnnotations => {
+                self.clear_annotations();
+                self.square_input.clear();
+                Command::none()
+            }
+            Message::SquareHovered(square) => {
+                self.hovered_square = Some(square);
+                Command::none()
+            }
+            Message::SquareUnhovered(square) => {
+                // The next square's enter may arrive first, so only clear our own hover
+                if self.hovered_square == Some(square) {
+                    self.hovered_square = None;
+                }
+                Command::none()
+            }
+            Message::RequestHint => {
+                if !self.is_human_turn() || self.hint_pending {
+                    return Command::none();
+                }
+                self.hint = None;
+                self.hint_pending = true;
+                get_hint(self.stockfish_path.clone(), self.search_position(), self.session_options())
+            }
+            Message::Hint(board, mv) => {
+                // A hint for a position that has since been left is no use
+                self.hint_pending = false;
+                if board == self.game.current_position() {
+                    self.hint = Some(mv);
+                    self.status = Some(format!("Hint: {}", move_to_san(&board, mv)));
+                }
+                Command::none()
+            }
+            Message::HintFailed(error) => {
+                self.hint_pending = false;
+                self.error = Some(format!("Engine error: {}", error));
+                Command::none()
+            }
+            Message::PositionChecked(ply, board, score) => {
+                // Taking moves back may have left the checked position behind
+                let reached = self.history.get(ply).map(Game::current_position);
+                let known = self.eval_history.iter().any(|&(p, _)| p == ply);
+                if let (Some(score), true, false) = (score, reached == Some(board), known) {
+                    let index = self.eval_history.partition_point(|&(p, _)| p < ply);
+                    self.eval_history.insert(index, (ply, white_relative_cp(score, board.side_to_move())));
+                }
+                Command::none()
+            }
+            Message::StartAnalysis => {
+                if self.is_human_turn() && self.engine_task.is_none() {
+                    self.analyzing = true;
+                }
+                Command::none()
+            }
+            Message::ToggleLayout => {
+                self.analysis_layout = !self.analysis_layout;
+                // The clocks pause in the analysis layout, and the pause isn't charged to anyone
+                self.last_tick = None;
+                self.selected_square = None;
+                self.promotion_pending = None;
+                self.premove = None;
+                self.premove_source = None;
+                if self.analysis_layout {
+                    // A search for the engine's move gives way to analysis of the position
+                    self.cancel_search();
+                    self.hand_over_to_side_to_move();
+                    return Command::none();
+                }
+                self.analyzing = false;
+                if self.game.result().is_some() {
+                    return Command::none();
+                }
+                self.begin_turn("Back to the game")
+            }
+            Message::StopAnalysis => {
+                self.analyzing = false;
+                Command::none()
+            }
+            Message::AnalysisUpdate(board, EngineReport { lines, stats, .. }) => {
+                // Updates still queued from a position that was left behind are dropped
+                if self.analysis_running() && board == self.game.current_position() {
+                    self.search_stats = Some(stats);
+                    self.set_engine_lines(lines, board);
+                }
+                Command::none()
+            }
+            Message::EngineProgress(_, EngineReport { lines, stats, engine_name, .. }) => {
+                // Reports can trail behind a search that was cancelled or already finished
+                if self.engine_task.is_some() {
+                    if engine_name.is_some() {
+                        self.engine_name = engine_name;
+                    }
+                    self.search_stats = Some(stats);
+                    self.set_engine_lines(lines, self.game.current_position());
+                }
+                Command::none()
+            }
+            Message::ThinkingTick => {
+                self.thinking_frame = (self.thinking_frame + 1) % (3 * THINKING_TICKS_PER_DOT);
+                Command::none()
+            }
+            Message::SetDebugView(enabled) => {
+                self.debug_view = enabled;
+                Command::none()
+            }
+            Message::ToggleTheme => {
+                self.theme = if self.is_dark() { Theme::Light } else { Theme::Dark };
+                self.save_config();
+                Command::none()
+            }
+            Message::SetTheme(theme) => {
+                self.theme = theme;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetMuted(muted) => {
+                self.muted = muted;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetUiScale(scale) => {
+                self.ui_scale = scale;
+                // No resize event follows a scale change, so refit the board here
+                self.fit_board();
+                self.save_config();
+                Command::none()
+            }
+            Message::SetLayout(layout) => {
+                self.layout = layout;
+                self.fit_board();
+                self.save_config();
+                Command::none()
+            }
+            Message::SetTurnChime(enabled) => {
+                self.turn_chime = enabled;
+                self.save_config();
+                Command::none()
+            }
+            Message::WindowFocused(focused) => {
+                self.window_focused = focused;
+                Command::none()
+            }
+            Message::ClaimDraw => {
+                if self.game.declare_draw() {
+                    self.cancel_search();
+                    self.draw_claimable = false;
+                    self.selected_square = None;
+                    self.promotion_pending = None;
+                    self.play_sound(SoundEvent::GameEnd);
+                }
+                Command::none()
+            }
+            Message::CopyFen => {
+                let fen = self.current_fen();
+                self.status = Some(format!("FEN copied to clipboard: {}", fen));
+                clipboard::write(fen)
+            }
+            Message::CopyPgn => {
+                let id = self.notice.as_ref().map_or(0, |(id, _)| id + 1);
+                self.notice = Some((id, "PGN copied to clipboard!".to_string()));
+                Command::batch([
+                    clipboard::write(self.build_pgn()),
+                    Command::perform(tokio::time::sleep(NOTICE_DURATION), move |()| Message::NoticeExpired(id)),
+                ])
+            }
+            Message::NoticeExpired(id) => {
+                // A newer notice keeps its own timer
+                if self.notice.as_ref().is_some_and(|(current, _)| *current == id) {
+                    self.notice = None;
+                }
+                Command::none()
+            }
+            Message::ExportPgn => {
+                let stamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default();
+                let path = PathBuf::from(format!("chess_gui_{}.pgn", stamp));
+                match std::fs::write(&path, self.build_pgn()) {
+                    Ok(()) => self.status = Some(format!("Game saved to {}", path.display())),
+                    Err(e) => self.error = Some(format!("Failed to save {}: {}", path.display(), e)),
+                }
+                Command::none()
+            }
+            Message::WindowResized(width, height) => {
+                // Sizes arrive already divided by the interface scale
+                let scale = self.scale_factor() as f32;
+                self.window_size = Some((width as f32 * scale, height as f32 * scale));
+                self.fit_board();
+                Command::none()
+            }
+            Message::SetEvalPerspective(perspective) => {
+                self.eval_perspective = perspective;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetBoardTheme(theme) => {
+                self.board_theme = theme;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetPieceStyle(style) => {
+                self.piece_style = style;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetTimeControl(time_control) => {
+                // Takes effect from the next New Game, or right away before the first move
+                self.time_control = time_control;
+                if self.history.is_empty() {
+                    self.reset_clocks();
+                }
+                Command::none()
+            }
+            Message::SetEngineUsesClock(enabled) => {
+                self.engine_uses_clock = enabled;
+                Command::none()
+            }
+            Message::Tick(now) => {
+                let elapsed = self.last_tick.map_or(Duration::ZERO, |last| now - last);
+                self.last_tick = Some(now);
+                let side = self.game.side_to_move();
+                let clock = match side {
+                    ChessColor::White => &mut self.white_time,
+                    ChessColor::Black => &mut self.black_time,
+                };
+                *clock = clock.saturating_sub(elapsed);
+                if clock.is_zero() && self.game.resign(side) {
+                    self.cancel_search();
+                    self.flagged = Some(side);
+                    self.selected_square = None;
+                    self.promotion_pending = None;
+                    self.play_sound(SoundEvent::GameEnd);
+                }
+                Command::none()
+            }
+            Message::Resign => {
+                self.cancel_search();
+                if self.game.resign(self.player_color) {
+                    self.selected_square = None;
+                    self.promotion_pending = None;
+                    self.play_sound(SoundEvent::GameEnd);
+                }
+                Command::none()
+            }
+        }
+    }
+
+    /// A widget per option the engine advertised, except those the app sets itself.
+    fn engine_options_panel(&self) -> Element<'_, Message> {
+        let options = self.advertised_options.iter()
+            .filter(|option| !MANAGED_OPTIONS.iter().any(|managed| option.name.eq_ignore_ascii_case(managed)))
+            .fold(Column::new().spacing(8), |column, option| {
+                let value = self.engine_options.get(&option.name).unwrap_or(&option.default);
+                let name = option.name.clone();
+                let label = Text::new(option.name.as_str()).size(14);
+                let widget: Element<Message> = match &option.kind {
+                    OptionKind::Check => Checkbox::new(option.name.as_str(), value == "true")
+                        .on_toggle(move |checked| Message::SetEngineOption(name.clone(), checked.to_string()))
+                        .text_size(14)
+                        .into(),
+                    &OptionKind::Spin { min, max } => {
+                        let current = value.parse::<i64>().unwrap_or(min).clamp(min, max);
+                        Column::new()
+                            .spacing(2)
+                            .push(Text::new(format!("{}: {}", option.name, current)).size(14))
+                            .push(
+                                Slider::new(min as f64..=max as f64, current as f64, move |v| {
+                                    Message::SetEngineOption(name.clone(), (v.round() as i64).to_string())
+                                })
+                                .width(250)
+                            )
+                            .into()
+                    }
+                    OptionKind::Combo(choices) => Row::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(label)
+                        .push(
+                            PickList::new(choices.clone(), Some(value.clone()), move |choice| {
+                                Message::SetEngineOption(name.clone(), choice)
+                            })
+                            .text_size(14)
+                        )
+                        .into(),
+                    OptionKind::String => Row::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(label)
+                        .push(
+                            TextInput::new("", value)
+                                .on_input(move |text| Message::SetEngineOption(name.clone(), text))
+                                .size(14)
+                                .width(150)
+                        )
+                        .into(),
+                    // Buttons trigger an action rather than hold a setting
+                    OptionKind::Button => return column,
+                };
+                column.push(widget)
+            });
+
+        Column::new()
+            .spacing(10)
+            .push(Scrollable::new(options).height(250))
+            .push(Button::new(Text::new("Reset to defaults").size(14)).on_press(Message::ResetEngineOptions))
+            .into()
+    }
+
+    /// How a piece on the board is drawn in the chosen style; letters are white or black like
+    /// the pieces they stand for.
+    fn piece_art(&self, color: ChessColor, piece: Piece) -> PieceArt {
+        match self.piece_style {
+            PieceStyle::Images => PieceArt::Image(piece_svg(color, piece)),
+            PieceStyle::Letters => PieceArt::Letter(
+                piece.to_string(color),
+                if color == ChessColor::White { Color::WHITE } else { Color::BLACK },
+            ),
+        }
+    }
+
+    /// A piece drawn off the board, filling its container. Letters keep the theme's text color,
+    /// with case telling the sides apart as in FEN.
+    fn piece_icon(&self, color: ChessColor, piece: Piece, letter_size: f32) -> Element<'_, Message> {
+        match self.piece_style {
+            PieceStyle::Images => Svg::new(piece_svg(color, piece)).width(Length::Fill).height(Length::Fill).into(),
+            PieceStyle::Letters => Text::new(piece.to_string(color))
+                .size(letter_size)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .horizontal_alignment(alignment::Horizontal::Center)
+                .vertical_alignment(alignment::Vertical::Center)
+                .into(),
+        }
+    }
+
+    /// Palette and actions shown in place of the game controls while setting up a position.
+    fn editor_panel(&self, editor: &PositionEditor) -> Element<'_, Message> {
+        let palette_button = |choice: Option<(Piece, ChessColor)>| {
+            let content: Element<Message> = match choice {
+                Some((piece, color)) => self.piece_icon(color, piece, 24.0),
+                None => Text::new("Erase").size(12).into(),
+            };
+            let background = if editor.palette == choice {
+                self.board_theme.selected
+            } else {
+                self.board_theme.light
+            };
+            Button::new(content)
+                .padding(4)
+                .width(45)
+                .height(45)
+                .style(iced::theme::Button::Custom(Box::new(ButtonStyle(background))))
+                .on_press(Message::SetPalette(choice))
+        };
+        let pieces = [Piece::King, Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+        let palette_row = |color: ChessColor| {
+            pieces.into_iter().fold(Row::new().spacing(5), |row, piece| {
+                row.push(palette_button(Some((piece, color))))
+            })
+        };
+
+        let side_to_move = [ChessColor::White, ChessColor::Black].into_iter().fold(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(Text::new("To move:").size(14)),
+            |row, color| {
+                row.push(
+                    Radio::new(color_name(color), color, Some(editor.side_to_move()), Message::SetEditSideToMove)
+                        .size(14)
+                        .text_size(14)
+                )
+            },
+        );
+
+        Column::new()
+            .spacing(15)
+            .push(Text::new(self.status_text()).size(18))
+            .push(Text::new("Pick a piece, then click squares to place it.").size(14))
+            .push(palette_row(ChessColor::White))
+            .push(palette_row(ChessColor::Black))
+            .push(palette_button(None))
+            .push(side_to_move)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Button::new("Clear").on_press(Message::ClearEditBoard))
+                    .push(Button::new("Start Position").on_press(Message::ResetEditBoard))
+                    .push(Button::new("Play").on_press(Message::FinishEdit))
+                    .push(Button::new("Cancel").on_press(Message::CancelEdit))
+            )
+            .into()
+    }
+
+    /// Replaces the game with `game` and starts play from it.
+    ///
+    /// The turn comes from the position itself, so a loaded position with the engine to move
+    /// starts its search straight away.
+    fn start_game(&mut self, game: Game, label: &str) -> Command<Message> {
+        self.reset_game(game);
+        self.begin_turn(label)
+    }
+
+    /// Replaces the game with `game`, clearing all per-game state.
+    fn reset_game(&mut self, game: Game) {
+        self.cancel_search();
+        self.reviewing = false;
+        self.player_color = self.next_player_color;
+        self.current_turn = game.side_to_move();
+        self.game = game;
+        self.status = None;
+        self.error = None;
+        self.game_over_dismissed = false;
+        self.history.clear();
+        self.move_history.clear();
+        self.san_history.clear();
+        self.clock_history.clear();
+        self.redo_stack.clear();
+        self.selected_square = None;
+        self.premove = None;
+        self.premove_source = None;
+        self.promotion_pending = None;
+        self.draw_offer = None;
+        self.draw_claimable = false;
+        self.insufficient_material = false;
+        self.confirming_new_game = false;
+        self.clear_annotations();
+        self.hint = None;
+        self.reset_clocks();
+        self.engine_lines.clear();
+        self.best_change = None;
+        self.eval_cp = None;
+        self.eval_history.clear();
+        self.search_stats = None;
+    }
+
+    fn status_text(&self) -> String {
+        match &self.status {
+            Some(note) => format!("{} - {}", note, self.game_status()),
+            None => self.game_status(),
+        }
+    }
+
+    /// The status line as the game and the app's activity make it, so it can't fall out of
+    /// step with the position.
+    fn game_status(&self) -> String {
+        let board = self.game.current_position();
+        let result = match self.game.result() {
+            Some(_) if self.flagged == Some(ChessColor::White) => Some("White loses on time. Black wins!"),
+            Some(_) if self.flagged == Some(ChessColor::Black) => Some("Black loses on time. White wins!"),
+            Some(_) if self.insufficient_material => Some("Draw — insufficient material"),
+            Some(GameResult::DrawAccepted) => Some("Draw accepted"),
+            Some(GameResult::WhiteResigns) => Some("White resigns. Black wins!"),
+            Some(GameResult::BlackResigns) => Some("Black resigns. White wins!"),
+            Some(GameResult::DrawDeclared) => Some("Draw declared"),
+            // Read off the board itself, so a side left without moves is announced even where
+            // the recorded result lags behind
+            Some(GameResult::WhiteCheckmates | GameResult::BlackCheckmates | GameResult::Stalemate) | None => {
+                no_moves_status(&board)
+            }
+        };
+        if let Some(result) = result {
+            return result.to_string();
+        }
+        if self.editor.is_some() {
+            return "Setting up a position".to_string();
+        }
+        if self.engine_task.is_some() {
+            return if self.draw_offer == Some(self.player_color) {
+                format!("Draw offered - {} is thinking", self.engine_name())
+            } else {
+                format!("{} is thinking", self.engine_name())
+            };
+        }
+        if self.analysis_running() {
+            return "Analyzing...".to_string();
+        }
+        if self.hint_pending {
+            return "Looking for a hint...".to_string();
+        }
+
+        let mut status = format!("{}'s turn", color_name(board.side_to_move()));
+        if board.checkers().popcnt() > 0 {
+            status.push_str(" - in check");
+        }
+        match self.draw_offer {
+            Some(side) if self.is_engine_side(side) => {
+                status = format!("{} offers a draw - {}", self.engine_name(), status);
+            }
+            Some(_) => status = format!("Draw offered - {}", status),
+            None => {}
+        }
+        if self.draw_claimable {
+            status.push_str(" - draw by repetition or 50-move rule can be claimed");
+        }
+        status
+    }
+
+    /// Gives the move to whichever side is to play, starting the engine's search if it's the
+    /// engine.
+    fn begin_turn(&mut self, label: &str) -> Command<Message> {
+        self.current_turn = self.game.side_to_move();
+        if self.is_engine_side(self.current_turn) {
+            return self.request_engine_move();
+        }
+        self.hand_over_to_side_to_move();
+        self.status = Some(label.to_string());
+        Command::none()
+    }
+
+    /// Keeps the saved game in step with the live one; finished and empty games aren't kept.
+    fn autosave(&mut self) {
+        // Until the resume prompt is answered, the file still holds last session's game
+        if self.resume_offer.is_some() {
+            return;
+        }
+        let current = (self.game.result().is_none() && !self.history.is_empty()).then(|| SavedGame {
+            start_fen: self.history[0].current_position().to_string(),
+            moves: self.move_history.iter().map(ChessMove::to_string).collect(),
+            human_plays_black: self.player_color == ChessColor::Black,
+            game_mode: self.game_mode.to_string(),
+            time_control: self.time_control.to_string(),
+        });
+        if current == self.last_saved {
+            return;
+        }
+        match &current {
+            Some(saved) => {
+                if let Err(error) = saved.save() {
+                    self.error = Some(format!("Failed to save the game: {}", error));
+                }
+            }
+            None => SavedGame::clear(),
+        }
+        self.last_saved = current;
+    }
+
+    /// Whether the engine should be analysing the position on the board.
+    fn analysis_running(&self) -> bool {
+        (self.analyzing || (self.analysis_layout && self.editor.is_none())) && self.game.result().is_none()
+    }
+
+    /// Whether the engine plays `color` in the current mode.
+    fn is_engine_side(&self, color: ChessColor) -> bool {
+        // While analysing or reviewing, the engine advises both sides and plays neither
+        if self.analysis_layout || self.reviewing {
+            return false;
+        }
+        match self.game_mode {
+            GameMode::HumanVsEngine => color != self.player_color,
+            GameMode::HumanVsHuman => false,
+            GameMode::EngineVsEngine => self.autoplaying,
+        }
+    }
+
+    /// Gives the human the side to move; in two-player mode that alternates between players.
+    fn hand_over_to_side_to_move(&mut self) {
+        self.player_color = self.game.side_to_move();
+        self.current_turn = self.player_color;
+    }
+
+    fn play_human_move(&mut self, mv: ChessMove) -> Command<Message> {
+        if !self.apply_move(mv) {
+            return Command::none();
+        }
+        // A new move branches off, so the undone line can no longer be redone
+        self.redo_stack.clear();
+        // Play picks up from here, with the engine back on its side
+        self.reviewing = false;
+        // The engine's reply takes over from any analysis of the old position
+        self.analyzing = false;
+        self.selected_square = None;
+        // Moving instead of accepting lets the engine's draw offer lapse
+        if self.draw_offer == Some(!self.player_color) {
+            self.draw_offer = None;
+        }
+        // Nothing to ask the engine once the human's move ends the game
+        if self.game.result().is_some() {
+            return Command::none();
+        }
+        if !self.is_engine_side(self.game.side_to_move()) {
+            self.hand_over_to_side_to_move();
+            return Command::none();
+        }
+        // The engine's reply evaluates the position after the move; the one before is known
+        // only if the engine's last move followed its main line, so otherwise it gets checked
+        let before = self.history.len() - 1;
+        let check = if self.eval_history.iter().any(|&(ply, _)| ply == before) {
+            Command::none()
+        } else {
+            let start = self.history[0].current_position();
+            let position =
+                SearchPosition::new(&start, &self.move_history[..before], self.history[before].current_position());
+            check_position(self.stockfish_path.clone(), before, position, self.session_options())
+        };
+        Command::batch([check, self.request_engine_move()])
+    }
+
+    /// Plays `mv` on the live game, recording the previous position and the move's SAN.
+    fn apply_move(&mut self, mv: ChessMove) -> bool {
+        let board = self.game.current_position();
+        let mut new_game = self.game.clone();
+        if !new_game.make_move(mv) {
+            return false;
+        }
+        self.history.push(std::mem::replace(&mut self.game, new_game));
+        self.move_history.push(mv);
+        self.san_history.push(move_to_san(&board, mv));
+        self.clock_history.push((self.white_time, self.black_time));
+        self.draw_claimable = self.game.can_declare_draw();
+        match board.side_to_move() {
+            ChessColor::White => self.white_time += self.time_control.increment,
+            ChessColor::Black => self.black_time += self.time_control.increment,
+        }
+        // Play stops in a dead position rather than shuffling kings forever
+        if self.game.result().is_none() && is_insufficient_material(&self.game.current_position()) {
+            let side = self.game.side_to_move();
+            self.insufficient_material = self.game.offer_draw(side) && self.game.accept_draw();
+        }
+        self.play_sound(move_sound(&board, mv, &self.game));
+        self.status = None;
+        self.clear_annotations();
+        self.hint = None;
+        true
+    }
+
+    /// Takes the game back to the position after `ply` moves, keeping the moves taken back for
+    /// redo and dropping everything that belonged to the later positions.
+    fn rewind_to(&mut self, ply: usize) {
+        self.game = self.history.split_off(ply).swap_remove(0);
+        let undone = self.move_history.drain(ply..);
+        self.redo_stack.extend(undone.rev());
+        self.san_history.truncate(ply);
+        if let Some(&(white, black)) = self.clock_history.get(ply) {
+            (self.white_time, self.black_time) = (white, black);
+            self.clock_history.truncate(ply);
+        }
+        // Should the game end again, its result deserves the banner again
+        self.game_over_dismissed = false;
+        self.clear_annotations();
+        self.hint = None;
+        self.selected_square = None;
+        self.premove = None;
+        self.premove_source = None;
+        self.promotion_pending = None;
+        self.draw_offer = None;
+        self.draw_claimable = self.game.can_declare_draw();
+        self.flagged = None;
+        self.insufficient_material = false;
+        self.last_tick = None;
+        self.engine_lines.clear();
+        self.best_change = None;
+        self.eval_cp = None;
+        self.eval_history.retain(|&(ply, _)| ply <= self.history.len());
+        self.search_stats = None;
+    }
+
+    /// The undone moves in SAN, in the order Redo would play them.
+    fn redo_san(&self) -> Vec<String> {
+        let moves: Vec<ChessMove> = self.redo_stack.iter().rev().copied().collect();
+        uci_pv_to_san(&self.game.current_position(), &moves)
+    }
+
+    fn clear_annotations(&mut self) {
+        self.arrows.clear();
+        self.marked_squares.clear();
+        self.annotation_start = None;
+    }
+
+    /// Whether a person may act on the board now: the side to move has been handed its turn
+    /// and the game mode doesn't give that side to the engine. Every move, hint, and analysis
+    /// request is gated on this, so each mode only needs describing in `is_engine_side`.
+    fn is_human_turn(&self) -> bool {
+        let side = self.game.side_to_move();
+        self.current_turn == side && !self.is_engine_side(side)
+    }
+
+    fn engine_name(&self) -> &str {
+        self.engine_name.as_deref().unwrap_or("Engine")
+    }
+
+    /// Whether the board is drawn from Black's side.
+    fn black_at_bottom(&self) -> bool {
+        // Two players share one fixed board unless it turns to face whoever is to move
+        let facing_black = match self.game_mode {
+            GameMode::HumanVsHuman if !self.auto_flip => false,
+            GameMode::EngineVsEngine => false,
+            _ => self.player_color == ChessColor::Black,
+        };
+        facing_black != self.flipped
+    }
+
+    fn is_dark(&self) -> bool {
+        self.theme.extended_palette().is_dark
+    }
+
+    /// Red for text that needs attention, lighter on a dark background so it stays legible.
+    fn alert_color(&self) -> Color {
+        if self.is_dark() { Color::from_rgb(1.0, 0.45, 0.45) } else { Color::from_rgb(0.8, 0.15, 0.15) }
+    }
+
+    fn play_sound(&self, event: SoundEvent) {
+        if let (Some(sounds), false) = (&self.sounds, self.muted) {
+            sounds.play(event);
+        }
+    }
+
+    fn reset_clocks(&mut self) {
+        self.white_time = self.time_control.base;
+        self.black_time = self.time_control.base;
+        self.last_tick = None;
+        self.flagged = None;
+    }
+
+    /// The side whose point of view engine scores are shown from.
+    fn eval_viewer(&self) -> ChessColor {
+        match self.eval_perspective {
+            EvalPerspective::SideToMove => self.analysis_position.side_to_move(),
+            EvalPerspective::Player if self.game_mode == GameMode::HumanVsEngine => self.player_color,
+            EvalPerspective::White | EvalPerspective::Player => ChessColor::White,
+        }
+    }
+
+    /// The engine reports scores for the side to move in the searched position; this shows them
+    /// for the chosen perspective instead.
+    fn score_text(&self, line: &EngineLine) -> String {
+        line.score_text(self.eval_viewer() != self.analysis_position.side_to_move())
+    }
+
+    /// Stores the engine's latest lines along with the position they were searched from.
+    fn set_engine_lines(&mut self, lines: Vec<EngineLine>, searched: Board) {
+        self.eval_cp = lines.first()
+            .map(|line| white_relative_cp(line.score, searched.side_to_move()));
+        // Deeper reports of the same position reveal when the engine changes its mind
+        let best = |lines: &[EngineLine]| lines.first().and_then(|line| line.pv.first().copied());
+        if searched != self.analysis_position {
+            self.best_change = None;
+        } else if let (Some(was), Some(new)) = (best(&self.engine_lines), best(&lines)) {
+            if new != was {
+                self.best_change = Some((new, was));
+            }
+        }
+        self.engine_lines = lines;
+        self.analysis_position = searched;
+    }
+
+    /// Sizes the squares to the last reported window for the current scale and layout.
+    fn fit_board(&mut self) {
+        if let Some((width, height)) = self.window_size {
+            let scale = self.scale_factor() as f32;
+            self.square_size =
+                square_size_for_window(width / scale, height / scale, self.layout, self.error.is_some());
+        }
+    }
+
+    fn save_config(&mut self) {
+        let config = Config {
+            engine_path: Some(self.stockfish_path.clone()),
+            move_time_ms: self.move_time_ms,
+            skill_level: self.skill_level,
+            contempt: self.contempt,
+            multipv: self.multipv,
+            eval_perspective: self.eval_perspective.to_string(),
+            board_theme: self.board_theme.name.to_string(),
+            piece_style: self.piece_style.to_string(),
+            muted: self.muted,
+            theme: self.theme.to_string(),
+            auto_flip: self.auto_flip,
+            autoplay_move_time_ms: self.autoplay_move_time_ms,
+            ui_scale_percent: self.ui_scale.0,
+            layout: self.layout.to_string(),
+            turn_chime: self.turn_chime,
+            engine_options: self.engine_options.clone(),
+            threads: self.threads,
+            hash_mb: self.hash_mb,
+        };
+        if let Err(error) = config.save() {
+            self.error = Some(format!("Failed to save settings: {}", error));
+        }
+    }
+
+    /// Renders the game so far as PGN, with the seven-tag roster and a terminating result.
+    fn build_pgn(&self) -> String {
+        let (white, black) = match (self.game_mode, self.player_color) {
+            (GameMode::HumanVsHuman, _) => ("Human", "Human"),
+            (GameMode::EngineVsEngine, _) => (self.engine_name(), self.engine_name()),
+            (_, ChessColor::White) => ("Human", self.engine_name()),
+            (_, ChessColor::Black) => (self.engine_name(), "Human"),
+        };
+        let result = match self.game.result() {
+            Some(GameResult::WhiteCheckmates) | Some(GameResult::BlackResigns) => "1-0",
+            Some(GameResult::BlackCheckmates) | Some(GameResult::WhiteResigns) => "0-1",
+            Some(GameResult::Stalemate)
+            | Some(GameResult::DrawAccepted)
+            | Some(GameResult::DrawDeclared) => "1/2-1/2",
+            None => "*",
+        };
+
+        let mut pgn = format!(
+            "[Event \"Casual\"]\n[Site \"Rust Chess\"]\n[Date \"????.??.??\"]\n[Round \"-\"]\n\
+             [White \"{}\"]\n[Black \"{}\"]\n[Result \"{}\"]\n",
+            white, black, result
+        );
+        // Games that didn't begin from the standard position record where they did begin
+        let start = self.history.first().unwrap_or(&self.game).current_position();
+        if start != Board::default() {
+            pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", start));
+        }
+        pgn.push('\n');
+
+        let black_first = self.starts_with_black();
+        let mut tokens = Vec::new();
+        for (ply, san) in self.san_history.iter().enumerate() {
+            let ply = ply + usize::from(black_first);
+            if ply % 2 == 0 {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            } else if tokens.is_empty() {
+                tokens.push(format!("{}...", ply / 2 + 1));
+            }
+            tokens.push(san.clone());
+        }
+        tokens.push(result.to_string());
+
+        // Keep movetext lines under 80 columns as the PGN export format recommends
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 79 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push('\n');
+        pgn
+    }
+
+    /// FEN of the live position, with the move counters the `chess` crate leaves at "0 1".
+    fn current_fen(&self) -> String {
+        let board = self.game.current_position().to_string();
+        let placement = board.strip_suffix(" 0 1").unwrap_or(&board);
+        format!("{} {} {}", placement, self.halfmove_clock(), self.fullmove_number())
+    }
+
+    /// Names the opening reached so far; the book only covers games from the initial position.
+    fn opening_text(&self) -> String {
+        let start = self.history.first().unwrap_or(&self.game).current_position();
+        if start != Board::default() {
+            return String::new();
+        }
+        match openings::classify(&self.move_history) {
+            BookStatus::InBook(Some(opening)) => format!("Opening: {} {}", opening.eco, opening.name),
+            BookStatus::InBook(None) => String::new(),
+            BookStatus::OutOfBook(Some(opening)) => {
+                format!("Opening: {} {} (out of book)", opening.eco, opening.name)
+            }
+            BookStatus::OutOfBook(None) => "Opening: out of book".to_string(),
+        }
+    }
+
+    /// "?" or "??" when the human's move reaching `ply` gave away evaluation, judged from
+    /// the engine's evaluations of the positions before and after it.
+    ///
+    /// Moves without an evaluation on both sides, such as one whose quick check hasn't come
+    /// back yet, go unjudged.
+    fn blunder_mark(&self, ply: usize) -> Option<&'static str> {
+        let mover = self.history.get(ply.checked_sub(1)?)?.side_to_move();
+        if mover != self.player_color {
+            return None;
+        }
+        let eval_at = |ply| self.eval_history.iter().find(|&&(p, _)| p == ply).map(|&(_, cp)| cp);
+        let (before, after) = (eval_at(ply - 1)?, eval_at(ply)?);
+        let loss = match mover {
+            ChessColor::White => before - after,
+            ChessColor::Black => after - before,
+        };
+        if loss >= BLUNDER_CP {
+            Some("??")
+        } else if loss >= MISTAKE_CP {
+            Some("?")
+        } else {
+            None
+        }
+    }
+
+    /// How many times the current position has occurred, counting this time.
+    fn repetition_count(&self) -> usize {
+        let board = self.game.current_position();
+        1 + self.history.iter()
+            .filter(|game| game.current_position() == board)
+            .count()
+    }
+
+    /// Whether the game's first move belongs to Black, e.g. when play began from a FEN.
+    fn starts_with_black(&self) -> bool {
+        self.history.first()
+            .map_or(self.game.side_to_move(), |game| game.side_to_move()) == ChessColor::Black
+    }
+
+    fn fullmove_number(&self) -> usize {
+        let black_first = self.starts_with_black();
+        1 + (self.history.len() + usize::from(black_first)) / 2
+    }
+
+    /// Plies since the last capture or pawn move, as counted by the 50-move rule.
+    fn halfmove_clock(&self) -> usize {
+        self.history.iter()
+            .zip(&self.move_history)
+            .rev()
+            .take_while(|(game, mv)| {
+                let board = game.current_position();
+                board.piece_on(mv.get_source()) != Some(Piece::Pawn)
+                    && board.piece_on(mv.get_dest()).is_none()
+            })
+            .count()
+    }
+
+    fn request_engine_move(&mut self) -> Command<Message> {
+        self.cancel_search();
+        self.reviewing = false;
+        // Against itself the engine takes whichever side is to move, leaving the other as the
+        // "player" it is answering
+        if self.game_mode == GameMode::EngineVsEngine {
+            self.player_color = !self.game.side_to_move();
+        }
+        let (handle, registration) = AbortHandle::new_pair();
+        self.engine_task = Some(handle);
+        let (force_move, stop) = oneshot::channel();
+        self.force_move = Some(force_move);
+        self.current_turn = !self.player_color;
+        self.thinking_frame = 0;
+        let limit = if self.engine_uses_clock && self.time_control.is_timed() && self.game_mode != GameMode::EngineVsEngine {
+            let increment = self.time_control.increment.as_millis() as u64;
+            SearchLimit::Clock {
+                wtime: self.white_time.as_millis() as u64,
+                btime: self.black_time.as_millis() as u64,
+                winc: increment,
+                binc: increment,
+            }
+        } else if self.game_mode == GameMode::EngineVsEngine {
+            SearchLimit::MoveTime(self.autoplay_move_time_ms)
+        } else {
+            SearchLimit::MoveTime(self.move_time_ms)
+        };
+        // Session options come last, so the user's choices win over the defaults before them
+        let mut options = vec![
+            ("Skill Level".to_string(), self.skill_level.to_string()),
+            ("Contempt".to_string(), self.contempt.to_string()),
+            ("UCI_LimitStrength".to_string(), "false".to_string()),
+            ("MultiPV".to_string(), self.multipv.to_string()),
+        ];
+        options.extend(self.session_options());
+        self.think_budget = match limit {
+            SearchLimit::MoveTime(millis) => Some((Instant::now(), Duration::from_millis(millis))),
+            SearchLimit::Clock { .. } => None,
+        };
+        get_stockfish_move(
+            self.stockfish_path.clone(),
+            self.search_position(),
+            limit,
+            options,
+            stop,
+            registration,
+            self.search_id,
+        )
+    }
+
+    /// The current position as the engine should see it, with the game's moves where possible.
+    fn search_position(&self) -> SearchPosition {
+        let start = self.history.first().unwrap_or(&self.game).current_position();
+        SearchPosition::new(&start, &self.move_history, self.game.current_position())
+    }
+
+    /// Options sent to every engine session: threads and hash size, then the user's own choices.
+    ///
+    /// The order is stable, so the list can be part of a subscription id.
+    fn session_options(&self) -> Vec<(String, String)> {
+        let mut options = vec![
+            ("Threads".to_string(), self.threads.to_string()),
+            ("Hash".to_string(), self.hash_mb.to_string()),
+        ];
+        options.extend(self.engine_options.iter().map(|(name, value)| (name.clone(), value.clone())));
+        options
+    }
+
+    /// Abandons any in-flight engine search or analysis, killing its process.
+    fn cancel_search(&mut self) {
+        self.search_id += 1;
+        if let Some(handle) = self.engine_task.take() {
+            handle.abort();
+        }
+        self.force_move = None;
+        self.think_budget = None;
+        self.analyzing = false;
+    }
+
+    /// How much of its think time the engine has used so far, from 0 to 1.
+    fn think_progress(&self) -> Option<f32> {
+        let (started, budget) = self.think_budget?;
+        if self.engine_task.is_none() || budget.is_zero() {
+            return None;
+        }
+        Some((started.elapsed().as_secs_f32() / budget.as_secs_f32()).min(1.0))
+    }
+}
+
+/// Maps a key press to its message: arrows steer the cursor, Enter/Space select, a file
+/// letter and rank digit name a square, Ctrl+Z undoes, Ctrl+Y redoes, Ctrl+N starts a new game,
+/// Shift+F flips the board, and Escape clears annotations and a half-typed square.
+fn shortcut(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    use keyboard::key::Named;
+
+    match key.as_ref() {
+        keyboard::Key::Named(Named::ArrowLeft) => Some(Message::MoveCursor(-1, 0)),
+        keyboard::Key::Named(Named::ArrowRight) => Some(Message::MoveCursor(1, 0)),
+        keyboard::Key::Named(Named::ArrowUp) => Some(Message::MoveCursor(0, 1)),
+        keyboard::Key::Named(Named::ArrowDown) => Some(Message::MoveCursor(0, -1)),
+        keyboard::Key::Named(Named::Enter | Named::Space) => Some(Message::SelectCursor),
+        keyboard::Key::Character("z") if modifiers.command() => Some(Message::Undo),
+        keyboard::Key::Character("y") if modifiers.command() => Some(Message::Redo),
+        keyboard::Key::Character("n") if modifiers.command() => Some(Message::NewGame),
+        // Plain "f" names the f-file, so flipping needs Shift
+        keyboard::Key::Character("F" | "f") if modifiers == keyboard::Modifiers::SHIFT => Some(Message::FlipBoard),
+        keyboard::Key::Character(typed) if modifiers.is_empty() => typed
+            .chars()
+            .next()
+            .filter(|key| matches!(key, 'a'..='h' | '1'..='8'))
+            .map(Message::SquareKey),
+        keyboard::Key::Named(Named::Escape) => Some(Message::ClearAnnotations),
+        _ => None,
+    }
+}
+
+/// Checkmate or stalemate when the side to move has no legal move, told apart by whether it
+/// is in check.
+fn no_moves_status(board: &Board) -> Option<&'static str> {
+    if MoveGen::new_legal(board).len() > 0 {
+        return None;
+    }
+    Some(match (board.checkers().popcnt() > 0, board.side_to_move()) {
+        (true, ChessColor::White) => "Black wins by checkmate!",
+        (true, ChessColor::Black) => "White wins by checkmate!",
+        (false, _) => "Draw by stalemate",
+    })
+}
+
+/// Picks the sound for a move from the position it was played in and the game after it.
+fn move_sound(before: &Board, mv: ChessMove, after: &Game) -> SoundEvent {
+    let piece = before.piece_on(mv.get_source());
+    let file_distance = (mv.get_source().get_file().to_index() as i32
+        - mv.get_dest().get_file().to_index() as i32).abs();
+    // En passant lands on an empty square, so a diagonal pawn step counts as a capture too
+    let is_capture = before.piece_on(mv.get_dest()).is_some()
+        || (piece == Some(Piece::Pawn) && file_distance == 1);
+
+    if after.result().is_some() || after.current_position().status() != BoardStatus::Ongoing {
+        SoundEvent::GameEnd
+    } else if after.current_position().checkers().popcnt() > 0 {
+        SoundEvent::Check
+    } else if piece == Some(Piece::King) && file_distance == 2 {
+        SoundEvent::Castle
+    } else if is_capture {
+        SoundEvent::Capture
+    } else {
+        SoundEvent::Move
+    }
+}
+
+/// Named engine strengths for casual play; the sliders stay available for fine-tuning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Difficulty {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+impl Difficulty {
+    const ALL: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert];
+
+    /// Skill level and think time in milliseconds.
+    fn settings(self) -> (u8, u64) {
+        match self {
+            Difficulty::Beginner => (3, 500),
+            Difficulty::Intermediate => (10, 1500),
+            Difficulty::Expert => (20, 5000),
+        }
+    }
+
+    fn matching(skill_level: u8, move_time_ms: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|difficulty| difficulty.settings() == (skill_level, move_time_ms))
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Who plays each side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameMode {
+    HumanVsEngine,
+    /// Two people taking turns at one board; the engine only helps with hints and analysis.
+    HumanVsHuman,
+    /// The engine plays both sides, move after move, while autoplay is running.
+    EngineVsEngine,
+}
+
+impl GameMode {
+    const ALL: [GameMode; 3] = [GameMode::HumanVsEngine, GameMode::HumanVsHuman, GameMode::EngineVsEngine];
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameMode::HumanVsEngine => write!(f, "Human vs Engine"),
+            GameMode::HumanVsHuman => write!(f, "Human vs Human"),
+            GameMode::EngineVsEngine => write!(f, "Engine vs Engine"),
+        }
+    }
+}
+
+/// Interface zoom in percent, for displays where the default sizes feel cramped or large.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct UiScale(u16);
+
+impl UiScale {
+    const ALL: [UiScale; 6] = [UiScale(75), UiScale(100), UiScale(125), UiScale(150), UiScale(175), UiScale(200)];
+}
+
+impl fmt::Display for UiScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// Where the controls and analysis go around the board, for different screens and for recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayoutMode {
+    /// Board on the left, everything else in a panel to its right.
+    Side,
+    /// Board centered with the panel underneath.
+    Below,
+    /// Nothing but the board and the layout picker.
+    BoardOnly,
+}
+
+impl LayoutMode {
+    const ALL: [LayoutMode; 3] = [LayoutMode::Side, LayoutMode::Below, LayoutMode::BoardOnly];
+}
+
+impl fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutMode::Side => write!(f, "Panel beside"),
+            LayoutMode::Below => write!(f, "Panel below"),
+            LayoutMode::BoardOnly => write!(f, "Board only"),
+        }
+    }
+}
+
+/// Starting time per side plus the increment added after every move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TimeControl {
+    base: Duration,
+    increment: Duration,
+}
+
+impl TimeControl {
+    const UNTIMED: TimeControl = TimeControl::new(0, 0);
+
+    const ALL: [TimeControl; 7] = [
+        Self::UNTIMED,
+        TimeControl::new(1, 0),
+        TimeControl::new(3, 2),
+        TimeControl::new(5, 3),
+        TimeControl::new(10, 0),
+        TimeControl::new(15, 10),
+        TimeControl::new(30, 0),
+    ];
+
+    const fn new(minutes: u64, increment_secs: u64) -> Self {
+        Self {
+            base: Duration::from_secs(minutes * 60),
+            increment: Duration::from_secs(increment_secs),
+        }
+    }
+
+    fn is_timed(&self) -> bool {
+        !self.base.is_zero()
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_timed() {
+            write!(f, "{}+{}", self.base.as_secs() / 60, self.increment.as_secs())
+        } else {
+            write!(f, "Untimed")
+        }
+    }
+}
+
+fn format_clock(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// Whose point of view engine scores are shown from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvalPerspective {
+    /// As the engine reports them, which flips sign from one move to the next.
+    SideToMove,
+    White,
+    /// The human's side against the engine; White when there is no single human side.
+    Player,
+}
+
+impl EvalPerspective {
+    const ALL: [EvalPerspective; 3] = [EvalPerspective::SideToMove, EvalPerspective::White, EvalPerspective::Player];
+}
+
+impl fmt::Display for EvalPerspective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalPerspective::SideToMove => write!(f, "Side to move"),
+            EvalPerspective::White => write!(f, "White"),
+            EvalPerspective::Player => write!(f, "Player"),
+        }
+    }
+}
+
+/// How pieces are drawn everywhere in the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PieceStyle {
+    /// The bundled piece artwork.
+    Images,
+    /// "K", "q" and so on, uppercase for White as in FEN, for when the artwork doesn't suit.
+    Letters,
+}
+
+impl PieceStyle {
+    const ALL: [PieceStyle; 2] = [PieceStyle::Images, PieceStyle::Letters];
+}
+
+impl fmt::Display for PieceStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PieceStyle::Images => write!(f, "Images"),
+            PieceStyle::Letters => write!(f, "Letters"),
+        }
+    }
+}
+
+/// Square colors used to paint the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoardTheme {
+    name: &'static str,
+    light: Color,
+    dark: Color,
+    selected: Color,
+    highlight: Color,
+}
+
+impl BoardTheme {
+    const CLASSIC: BoardTheme = BoardTheme {
+        name: "Classic",
+        light: Color::from_rgb(0.94, 0.85, 0.71),
+        dark: Color::from_rgb(0.71, 0.53, 0.39),
+        selected: Color::from_rgb(0.8, 0.8, 0.3),
+        highlight: Color::from_rgb(0.6, 0.75, 0.4),
+    };
+    const BLUE: BoardTheme = BoardTheme {
+        name: "Blue",
+        light: Color::from_rgb(0.87, 0.89, 0.90),
+        dark: Color::from_rgb(0.55, 0.64, 0.68),
+        selected: Color::from_rgb(0.4, 0.7, 0.9),
+        highlight: Color::from_rgb(0.5, 0.75, 0.65),
+    };
+    const GREEN: BoardTheme = BoardTheme {
+        name: "Green",
+        light: Color::from_rgb(0.93, 0.93, 0.82),
+        dark: Color::from_rgb(0.46, 0.59, 0.34),
+        selected: Color::from_rgb(0.73, 0.79, 0.17),
+        highlight: Color::from_rgb(0.85, 0.75, 0.35),
+    };
+    const GRAY: BoardTheme = BoardTheme {
+        name: "Gray",
+        light: Color::from_rgb(0.73, 0.73, 0.73),
+        dark: Color::from_rgb(0.25, 0.25, 0.25),
+        selected: Color::from_rgb(0.7, 0.7, 0.0),
+        highlight: Color::from_rgb(0.35, 0.65, 0.35),
+    };
+
+    const ALL: [BoardTheme; 4] = [Self::CLASSIC, Self::BLUE, Self::GREEN, Self::GRAY];
+}
+
+impl fmt::Display for BoardTheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+struct FillStyle(Color);
+impl iced::widget::container::StyleSheet for FillStyle {
+    type Style = iced::Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> iced::widget::container::Appearance {
+        iced::widget::container::Appearance {
+            background: Some(self.0.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Editor palette button style: a square-colored background showing which piece is chosen.
+struct ButtonStyle(Color);
+impl iced::widget::button::StyleSheet for ButtonStyle {
+    type Style = iced::Theme;
+
+    fn active(&self, _style: &Self::Style) -> iced::widget::button::Appearance {
+        iced::widget::button::Appearance {
+            background: Some(self.0.into()),
+            border: iced::Border {
+                radius: 3.0.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+const DEFAULT_SQUARE_SIZE: f32 = 70.0;
+/// How long a confirmation such as "PGN copied" stays up.
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
+/// Redraw rate while the engine thinks, often enough for the progress bar to move smoothly.
+const THINKING_TICK: Duration = Duration::from_millis(100);
+/// The thinking dots advance every this many ticks.
+const THINKING_TICKS_PER_DOT: usize = 4;
+const CURSOR_OUTLINE: Color = Color::from_rgb(0.1, 0.4, 0.9);
+const PREMOVE_COLOR: Color = Color::from_rgb(0.75, 0.5, 0.8);
+const TURN_INDICATOR_COLOR: Color = Color::from_rgb(0.2, 0.7, 0.3);
+const EN_PASSANT_COLOR: Color = Color::from_rgb(0.35, 0.65, 0.85);
+const HINT_COLOR: Color = Color::from_rgb(0.45, 0.8, 0.45);
+const MARKED_SQUARE_COLOR: Color = Color::from_rgb(0.85, 0.4, 0.35);
+const ERROR_BANNER_COLOR: Color = Color::from_rgb(0.7, 0.15, 0.15);
+/// Tints mixed into the hovered square while a piece is selected, for a legal or illegal move.
+const LEGAL_PREVIEW_COLOR: Color = Color::from_rgb(0.2, 0.8, 0.2);
+const ILLEGAL_PREVIEW_COLOR: Color = Color::from_rgb(0.9, 0.15, 0.15);
+/// Engine options driven by the app's own difficulty and analysis settings.
+const MANAGED_OPTIONS: [&str; 7] =
+    ["Skill Level", "Contempt", "MultiPV", "UCI_LimitStrength", "UCI_Elo", "Threads", "Hash"];
+/// Horizontal room reserved for the controls and analysis panel beside the board.
+const PANEL_WIDTH: f32 = 420.0;
+/// Window padding and the gaps between squares, along either axis.
+const BOARD_CHROME: f32 = 2.0 * 30.0 + 7.0 * 5.0;
+/// Beside the board: the rank labels, and the eval bar with its spacing.
+const BOARD_CHROME_WIDTH: f32 = BOARD_CHROME + (15.0 + 5.0) + (20.0 + 30.0);
+/// Above and below the board: the file labels, the capture rows, and the status bar.
+const BOARD_CHROME_HEIGHT: f32 = BOARD_CHROME + (20.0 + 5.0) + 2.0 * (24.0 + 5.0) + 24.0;
+/// Room taken from the top of the window while the error banner shows.
+const ERROR_BANNER_HEIGHT: f32 = 40.0;
+
+/// Fits the board to the smaller window dimension so it stays fully visible.
+fn square_size_for_window(width: f32, height: f32, layout: LayoutMode, banner: bool) -> f32 {
+    // Below the board the panel scrolls, so only the side layout takes room from the width
+    let width = if layout == LayoutMode::Side { width - PANEL_WIDTH } else { width };
+    let height = if banner { height - ERROR_BANNER_HEIGHT } else { height };
+    let available = (width - BOARD_CHROME_WIDTH).min(height - BOARD_CHROME_HEIGHT);
+    (available / 8.0).clamp(30.0, 140.0)
+}
+
+/// The engine accepts (and late in the game offers) draws when the position is this level.
+const DRAW_ACCEPT_THRESHOLD_CP: i32 = 30;
+/// Plies played before the engine starts offering draws itself.
+const ENGINE_DRAW_OFFER_MIN_PLIES: usize = 60;
+
+/// Conventional piece values in pawns; kings are never counted.
+fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 0,
+    }
+}
+
+/// White's material minus Black's, in pawns.
+fn material_diff(board: &Board) -> i32 {
+    [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| {
+            let pieces = *board.pieces(piece);
+            let white = (pieces & *board.color_combined(ChessColor::White)).popcnt() as i32;
+            let black = (pieces & *board.color_combined(ChessColor::Black)).popcnt() as i32;
+            piece_value(piece) * (white - black)
+        })
+        .sum()
+}
+
+/// Whether neither side has the material left to deliver mate: bare kings, a single minor
+/// piece, or only bishops that all stand on squares of one color.
+fn is_insufficient_material(board: &Board) -> bool {
+    let heavy = *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    if heavy.popcnt() > 0 {
+        return false;
+    }
+    let knights = board.pieces(Piece::Knight).popcnt();
+    let bishops = *board.pieces(Piece::Bishop);
+    if knights + bishops.popcnt() <= 1 {
+        return true;
+    }
+    let light = |square: Square| (square.get_file().to_index() + square.get_rank().to_index()) % 2 == 1;
+    knights == 0 && {
+        let mut colors = bishops.map(light);
+        let first = colors.next();
+        colors.all(|color| Some(color) == first)
+    }
+}
+
+/// Opponent pieces `color` has taken since the game's start, most valuable first.
+///
+/// They are read off the moves played rather than counted against a full set, so a game begun
+/// from a sparse position or one with promotions only shows what was really captured.
+fn captured_by(history: &[Game], moves: &[ChessMove], color: ChessColor) -> Vec<Piece> {
+    let mut captured: Vec<Piece> = history.iter()
+        .map(Game::current_position)
+        .zip(moves)
+        .filter(|(board, _)| board.side_to_move() == color)
+        .filter_map(|(board, mv)| {
+            // En passant is the one capture landing on an empty square
+            let diagonal_pawn_move = board.piece_on(mv.get_source()) == Some(Piece::Pawn)
+                && mv.get_source().get_file() != mv.get_dest().get_file();
+            board.piece_on(mv.get_dest()).or(diagonal_pawn_move.then_some(Piece::Pawn))
+        })
+        .collect();
+    captured.sort_by_key(|piece| std::cmp::Reverse(piece.to_index()));
+    captured
+}
+
+/// Centipawns at which the evaluation bar is completely filled by one side.
+const EVAL_BAR_LIMIT_CP: i32 = 1000;
+/// Evaluation a move must give away, in centipawns, to be marked "?" or "??".
+const MISTAKE_CP: i32 = 150;
+const BLUNDER_CP: i32 = 300;
+
+/// Count of the side to move's legal moves; none left means the game has ended on the board.
+fn legal_moves_text(board: &Board) -> String {
+    match MoveGen::new_legal(board).len() {
+        0 if board.checkers().popcnt() > 0 => "No legal moves: checkmate".to_string(),
+        0 => "No legal moves: stalemate".to_string(),
+        1 => "1 legal move".to_string(),
+        count => format!("{} legal moves", count),
+    }
+}
+
+/// Converts an engine score for `side` into White's point of view, pinning mates to the bar ends.
+fn white_relative_cp(score: Score, side: ChessColor) -> i32 {
+    let cp = match score {
+        Score::Cp(cp) => cp,
+        Score::Mate(moves) if moves > 0 => EVAL_BAR_LIMIT_CP,
+        Score::Mate(_) => -EVAL_BAR_LIMIT_CP,
+    };
+    if side == ChessColor::White { cp } else { -cp }
+}
+
+fn engine_accepts_draw(lines: &[EngineLine]) -> bool {
+    lines.first()
+        .is_some_and(|line| matches!(line.score, Score::Cp(cp) if cp.abs() < DRAW_ACCEPT_THRESHOLD_CP))
+}
+
+fn color_name(color: ChessColor) -> &'static str {
+    match color {
+        ChessColor::White => "White",
+        ChessColor::Black => "Black",
+    }
+}
+
+/// Piece artwork bundled into the binary, so rendering never depends on installed fonts.
+fn piece_svg(color: ChessColor, piece: Piece) -> svg::Handle {
+    svg::Handle::from_memory(piece_svg_bytes(color, piece))
+}
+
+fn piece_svg_bytes(color: ChessColor, piece: Piece) -> &'static [u8] {
+    match (color, piece) {
+        (ChessColor::White, Piece::King) => include_bytes!("../assets/pieces/wK.svg"),
+        (ChessColor::White, Piece::Queen) => include_bytes!("../assets/pieces/wQ.svg"),
+        (ChessColor::White, Piece::Rook) => include_bytes!("../assets/pieces/wR.svg"),
+        (ChessColor::White, Piece::Bishop) => include_bytes!("../assets/pieces/wB.svg"),
+        (ChessColor::White, Piece::Knight) => include_bytes!("../assets/pieces/wN.svg"),
+        (ChessColor::White, Piece::Pawn) => include_bytes!("../assets/pieces/wP.svg"),
+        (ChessColor::Black, Piece::King) => include_bytes!("../assets/pieces/bK.svg"),
+        (ChessColor::Black, Piece::Queen) => include_bytes!("../assets/pieces/bQ.svg"),
+        (ChessColor::Black, Piece::Rook) => include_bytes!("../assets/pieces/bR.svg"),
+        (ChessColor::Black, Piece::Bishop) => include_bytes!("../assets/pieces/bB.svg"),
+        (ChessColor::Black, Piece::Knight) => include_bytes!("../assets/pieces/bN.svg"),
+        (ChessColor::Black, Piece::Pawn) => include_bytes!("../assets/pieces/bP.svg"),
+    }
+}
+
+/// Mixes `tint` half and half into `base`.
+fn blend(base: Color, tint: Color) -> Color {
+    Color::from_rgb((base.r + tint.r) / 2.0, (base.g + tint.g) / 2.0, (base.b + tint.b) / 2.0)
+}
+
+/// Adds `item` if absent and removes it otherwise.
+fn toggle<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    match items.iter().position(|existing| *existing == item) {
+        Some(index) => {
+            items.remove(index);
+        }
+        None => items.push(item),
+    }
+}
+
+/// Logical cores on this machine, the most threads worth giving the engine.
+fn max_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// The largest hash worth allowing: half the physical memory, when the OS reports it.
+fn max_hash_mb() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let total_kb: usize = meminfo.lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(total_kb / 1024 / 2)
+}
+
+/// Finds the engine binary: `CHESS_GUI_STOCKFISH` first, then the saved setting, then
+/// `stockfish` on the `PATH`, then the historical default location.
+fn resolve_stockfish_path(configured: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = std::env::var_os("CHESS_GUI_STOCKFISH") {
+        return PathBuf::from(path);
+    }
+    if let Some(path) = configured {
+        return path;
+    }
+
+    let binary = format!("stockfish{}", std::env::consts::EXE_SUFFIX);
+    if let Some(found) = std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(&binary))
+            .find(|candidate| candidate.is_file())
+    }) {
+        return found;
+    }
+
+    PathBuf::from("/usr/local/bin/stockfish")
+}
+
+/// Whether moving `from` to `to` needs a promotion piece. Legality is checked with the piece
+/// already chosen, so diagonal captures onto the last rank count as well as straight pushes.
+fn is_promotion(board: &Board, from: Square, to: Square) -> bool {
+    board.piece_on(from) == Some(Piece::Pawn)
+        && (to.get_rank() == Rank::Eighth || to.get_rank() == Rank::First)
+        && board.legal(ChessMove::new(from, to, Some(Piece::Queen)))
+}
+
+/// Formats `mv` in standard algebraic notation for the position `board` it is played from.
+fn move_to_san(board: &Board, mv: ChessMove) -> String {
+    let from = mv.get_source();
+    let to = mv.get_dest();
+    let piece = board.piece_on(from).unwrap_or(Piece::Pawn);
+
+    let mut san = if piece == Piece::King && from.get_file().to_index().abs_diff(to.get_file().to_index()) == 2 {
+        if to.get_file() == File::G { "O-O".to_string() } else { "O-O-O".to_string() }
+    } else {
+        let is_capture = board.piece_on(to).is_some()
+            || (piece == Piece::Pawn && from.get_file() != to.get_file());
+        let mut san = String::new();
+
+        if piece == Piece::Pawn {
+            if is_capture {
+                san.push(file_char(from.get_file()));
+            }
+        } else {
+            san.push_str(&piece.to_string(ChessColor::White));
+
+            // Disambiguate between identical pieces that can reach the same square
+            let rivals: Vec<Square> = MoveGen::new_legal(board)
+                .filter(|other| {
+                    other.get_dest() == to
+                        && other.get_source() != from
+                        && board.piece_on(other.get_source()) == Some(piece)
+                })
+                .map(|other| other.get_source())
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|sq| sq.get_file() != from.get_file()) {
+                    san.push(file_char(from.get_file()));
+                } else if rivals.iter().all(|sq| sq.get_rank() != from.get_rank()) {
+                    san.push_str(&(from.get_rank().to_index() + 1).to_string());
+                } else {
+                    san.push_str(&from.to_string());
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&to.to_string());
+        if let Some(promotion) = mv.get_promotion() {
+            san.push('=');
+            san.push_str(&promotion.to_string(ChessColor::White));
+        }
+        san
+    };
+
+    let after = board.make_move_new(mv);
+    if after.checkers().popcnt() > 0 {
+        san.push(if MoveGen::new_legal(&after).len() == 0 { '#' } else { '+' });
+    }
+    san
+}
+
+/// Converts an engine line into SAN by playing it out from `start`, stopping at the
+/// first move that is not legal in the resulting position.
+fn uci_pv_to_san(start: &Board, moves: &[ChessMove]) -> Vec<String> {
+    let mut board = *start;
+    let mut line = Vec::with_capacity(moves.len());
+    for &mv in moves {
+        if !board.legal(mv) {
+            break;
+        }
+        line.push(move_to_san(&board, mv));
+        board = board.make_move_new(mv);
+    }
+    line
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+fn get_stockfish_move(
+    path: PathBuf,
+    position: SearchPosition,
+    limit: SearchLimit,
+    options: Vec<(String, String)>,
+    mut stop: oneshot::Receiver<()>,
+    registration: AbortRegistration,
+    search_id: u64,
+) -> Command<Message> {
+    // Progress and the final result share one channel so they arrive in order
+    let (mut output, messages) = mpsc::channel(100);
+    let search = async move {
+        let search = async {
+            let mut engine = EngineSession::start(&path).await?;
+            let mut commands = engine.option_commands(&borrowed_options(&options));
+            commands.push_str(&format!("isready\nucinewgame\n{}{}", position.command(), limit.go_command()));
+            engine.send(&commands).await?;
+
+            let mut report = EngineReport {
+                engine_name: engine.profile.name.take(),
+                ..EngineReport::default()
+            };
+            let read = async {
+                let mut listening = true;
+                loop {
+                    tokio::select! {
+                        forced = &mut stop, if listening => {
+                            listening = false;
+                            // The engine answers `stop` with the best move it has so far; a
+                            // dropped sender only means nobody will ask
+                            if forced.is_ok() {
+                                engine.send("stop\n").await?;
+                            }
+                        }
+                        line = engine.read_line() => {
+                            let Some(line) = line? else {
+                                return Ok::<_, String>(false);
+                            };
+                            if line.starts_with("info") && report.record_info(&line) {
+                                let _ = output.send(Message::EngineProgress(search_id, report.clone())).await;
+                            }
+                            if report.record_best_move(&line) {
+                                return Ok(true);
+                            }
+                        }
+                    }
+                }
+            };
+            let budget = limit.time_budget(position.board.side_to_move());
+            let finished = match tokio::time::timeout(budget, read).await {
+                Ok(finished) => finished?,
+                Err(_) => {
+                    engine.quit().await;
+                    return Err("engine timed out".to_string());
+                }
+            };
+
+            if !finished {
+                return Err(engine.exit_error("engine exited without a best move").await);
+            }
+            engine.quit().await;
+            Ok(report)
+        };
+        let result = search.await;
+        let _ = output.send(Message::EngineMove(search_id, result)).await;
+    };
+
+    // The search itself only drives the engine; everything it reports comes through the channel.
+    // An aborted search yields no further messages, and dropping it kills the engine process
+    let driver = stream::once(search).filter_map(|()| future::ready(None));
+    Command::run(Abortable::new(stream::select(messages, driver), registration), std::convert::identity)
+}
+
+/// Think time for the Hint button: enough for a sound suggestion without a long wait.
+const HINT_MOVE_TIME_MS: u64 = 1000;
+
+/// Runs a short full-strength search for the Hint button, reporting only the best move.
+fn get_hint(path: PathBuf, position: SearchPosition, session_options: Vec<(String, String)>) -> Command<Message> {
+    let board = position.board;
+    let search = async move {
+        let mut engine = EngineSession::start(&path).await?;
+        let mut commands = engine.option_commands(&[
+            ("Skill Level", "20".to_string()),
+            ("UCI_LimitStrength", "false".to_string()),
+        ]);
+        commands.push_str(&engine.option_commands(&borrowed_options(&session_options)));
+        let limit = SearchLimit::MoveTime(HINT_MOVE_TIME_MS);
+        commands.push_str(&format!("isready\nucinewgame\n{}{}", position.command(), limit.go_command()));
+        engine.send(&commands).await?;
+
+        let mut report = EngineReport::default();
+        let read = async {
+            while let Some(line) = engine.read_line().await? {
+                if report.record_best_move(&line) {
+                    return Ok(true);
+                }
+            }
+            Ok::<_, String>(false)
+        };
+        let finished = tokio::time::timeout(limit.time_budget(board.side_to_move()), read).await
+            .map_err(|_| "engine timed out".to_string())??;
+        if !finished {
+            return Err(engine.exit_error("engine exited without a best move").await);
+        }
+        engine.quit().await;
+        report.best_move.ok_or_else(|| "engine found no move to suggest".to_string())
+    };
+    Command::perform(search, move |result| match result {
+        Ok(mv) => Message::Hint(board, mv),
+        Err(error) => Message::HintFailed(error),
+    })
+}
+
+/// Think time for the check of the position a human move was played from.
+const POSITION_CHECK_MOVE_TIME_MS: u64 = 300;
+/// Hash table size in MB for that check.
+const POSITION_CHECK_HASH_MB: u32 = 16;
+
+/// Runs a short full-strength search of the position at `ply`, reporting only its main line's
+/// score; a failed check just leaves the move played from it unjudged.
+fn check_position(
+    path: PathBuf,
+    ply: usize,
+    position: SearchPosition,
+    mut session_options: Vec<(String, String)>,
+) -> Command<Message> {
+    let board = position.board;
+    let search = async move {
+        let mut engine = EngineSession::start(&path).await?;
+        let mut commands = engine.option_commands(&[
+            ("Skill Level", "20".to_string()),
+            ("UCI_LimitStrength", "false".to_string()),
+            ("MultiPV", "1".to_string()),
+        ]);
+        // The check runs beside the engine's reply, so it keeps to one thread and a small
+        // table rather than competing with the reply for the user's Threads and Hash
+        commands.push_str(&engine.option_commands(&[
+            ("Threads", "1".to_string()),
+            ("Hash", POSITION_CHECK_HASH_MB.to_string()),
+        ]));
+        session_options.retain(|(name, _)| name != "Threads" && name != "Hash");
+        commands.push_str(&engine.option_commands(&borrowed_options(&session_options)));
+        let limit = SearchLimit::MoveTime(POSITION_CHECK_MOVE_TIME_MS);
+        commands.push_str(&format!("isready\nucinewgame\n{}{}", position.command(), limit.go_command()));
+        engine.send(&commands).await?;
+
+        let mut report = EngineReport::default();
+        let read = async {
+            while let Some(line) = engine.read_line().await? {
+                if line.starts_with("info") {
+                    report.record_info(&line);
+                } else if report.record_best_move(&line) {
+                    return Ok(true);
+                }
+            }
+            Ok::<_, String>(false)
+        };
+        let finished = tokio::time::timeout(limit.time_budget(board.side_to_move()), read).await
+            .map_err(|_| "engine timed out".to_string())??;
+        engine.quit().await;
+        Ok::<_, String>(if finished { report.lines.first().map(|line| line.score) } else { None })
+    };
+    Command::perform(search, move |result| Message::PositionChecked(ply, board, result.ok().flatten()))
+}
+
+/// Streams the engine's lines for `position` from an open-ended `go infinite` search.
+///
+/// The analysis runs for as long as the subscription is active; when it is dropped, because
+/// analysis was stopped or the position changed, the engine process goes with it.
+fn analysis_subscription(
+    path: PathBuf,
+    position: SearchPosition,
+    multipv: u8,
+    session_options: Vec<(String, String)>,
+) -> Subscription<Message> {
+    let board = position.board;
+    subscription::channel(
+        ("analysis", path.clone(), position.clone(), multipv, session_options.clone()),
+        100,
+        move |mut output| async move {
+            let analysis = async {
+                let mut engine = EngineSession::start(&path).await?;
+                let mut commands = engine.option_commands(&[
+                    ("Skill Level", "20".to_string()),
+                    ("UCI_LimitStrength", "false".to_string()),
+                    ("MultiPV", multipv.to_string()),
+                ]);
+                commands.push_str(&engine.option_commands(&borrowed_options(&session_options)));
+                commands.push_str(&format!("isready\nucinewgame\n{}go infinite\n", position.command()));
+                engine.send(&commands).await?;
+
+                let mut report = EngineReport::default();
+                while let Some(line) = engine.read_line().await? {
+                    if line.starts_with("info") && report.record_info(&line) {
+                        let _ = output.send(Message::AnalysisUpdate(board, report.clone())).await;
+                    }
+                }
+                Err::<(), _>(engine.exit_error("engine exited during analysis").await)
+            };
+            if let Err(error) = analysis.await {
+                let _ = output.send(Message::EngineError(error)).await;
+            }
+            future::pending().await
+        },
+    )
+}
+
+fn borrowed_options(options: &[(String, String)]) -> Vec<(&str, String)> {
+    options.iter().map(|(name, value)| (name.as_str(), value.clone())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_promotion_opens_the_chooser() {
+        let board = Board::from_str("2n1k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(is_promotion(&board, Square::B7, Square::C8));
+        // The new queen checks the king along the eighth rank
+        let mv = ChessMove::new(Square::B7, Square::C8, Some(Piece::Queen));
+        assert_eq!(move_to_san(&board, mv), "bxc8=Q+");
+    }
+
+    #[test]
+    fn captures_count_against_the_actual_start() {
+        // A sparse start, a promotion that takes nothing, then a real capture of the knight
+        let mut game = Game::new_with_board(Board::from_str("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+        let moves = [
+            ChessMove::new(Square::A7, Square::A8, Some(Piece::Queen)),
+            ChessMove::new(Square::E8, Square::F7, None),
+            ChessMove::new(Square::A8, Square::B8, None),
+        ];
+        let mut history = Vec::new();
+        for mv in moves {
+            history.push(game.clone());
+            assert!(game.make_move(mv));
+        }
+        assert_eq!(captured_by(&history, &moves, ChessColor::White), [Piece::Knight]);
+        assert!(captured_by(&history, &moves, ChessColor::Black).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use chess::ChessMove;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// A named opening and the moves, from the initial position, that define it.
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    moves: Vec<ChessMove>,
+}
+
+/// How the moves played so far relate to the opening book.
+pub enum BookStatus {
+    /// Still following book lines; the opening named is the longest one reached.
+    InBook(Option<&'static Opening>),
+    /// No book line continues this way; the opening named is the last one passed through.
+    OutOfBook(Option<&'static Opening>),
+}
+
+/// The bundled ECO table, parsed on first use.
+fn book() -> &'static [Opening] {
+    static BOOK: OnceLock<Vec<Opening>> = OnceLock::new();
+    BOOK.get_or_init(|| {
+        include_str!("../assets/openings.tsv")
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let eco = fields.next()?;
+                let name = fields.next()?;
+                let moves = fields.next()?
+                    .split_whitespace()
+                    .map(ChessMove::from_str)
+                    .collect::<Result<_, _>>()
+                    .ok()?;
+                Some(Opening { eco, name, moves })
+            })
+            .collect()
+    })
+}
+
+/// Matches a game's moves, from the standard starting position, against the book.
+pub fn classify(played: &[ChessMove]) -> BookStatus {
+    let book = book();
+    let deepest = book.iter()
+        .filter(|opening| played.starts_with(&opening.moves))
+        .max_by_key(|opening| opening.moves.len());
+    if book.iter().any(|opening| opening.moves.starts_with(played)) {
+        BookStatus::InBook(deepest)
+    } else {
+        BookStatus::OutOfBook(deepest)
+    }
+}
@@ -0,0 +1,59 @@
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+use std::time::Duration;
+
+/// Game events that get their own sound effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    Move,
+    Capture,
+    Castle,
+    Check,
+    GameEnd,
+    /// The engine has moved and the player is to move.
+    YourTurn,
+}
+
+impl SoundEvent {
+    /// Tones as (frequency in Hz, duration in ms), played one after another.
+    fn tones(self) -> &'static [(f32, u64)] {
+        match self {
+            SoundEvent::Move => &[(660.0, 60)],
+            SoundEvent::Capture => &[(330.0, 110)],
+            SoundEvent::Castle => &[(660.0, 50), (880.0, 50)],
+            SoundEvent::Check => &[(880.0, 80), (1175.0, 120)],
+            SoundEvent::GameEnd => &[(784.0, 150), (659.0, 150), (523.0, 300)],
+            // Starts after a pause so it follows the engine's move sound instead of masking it
+            SoundEvent::YourTurn => &[(0.0, 150), (1047.0, 90), (1319.0, 180)],
+        }
+    }
+}
+
+/// Owns the audio output; the stream must stay alive for anything to be heard.
+pub struct Sounds {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+impl Sounds {
+    /// Opens the default output device, or returns `None` when there is none.
+    pub fn new() -> Option<Self> {
+        let (stream, handle) = OutputStream::try_default().ok()?;
+        Some(Self { _stream: stream, handle })
+    }
+
+    /// Queues the event's tones without blocking the UI.
+    pub fn play(&self, event: SoundEvent) {
+        let Ok(sink) = Sink::try_new(&self.handle) else {
+            return;
+        };
+        for &(frequency, millis) in event.tones() {
+            sink.append(
+                SineWave::new(frequency)
+                    .take_duration(Duration::from_millis(millis))
+                    .amplify(0.2),
+            );
+        }
+        sink.detach();
+    }
+}